@@ -229,6 +229,7 @@ fn main() {
 
     let buffer_width = db.size().0;
     let buffer_height = db.size().1;
+    let buffer_pitch = db.pitch();
 
     // Set the crtc
     // On many setups, this requires root access.
@@ -239,8 +240,10 @@ fn main() {
     let mut map = card
             .map_dumb_buffer(&mut db)
             .expect("Could not map dumbbuffer");
-    let mut buffer = Buffer::map(buffer_width, buffer_height, Format::RGBA8888, fd.into_raw_fd(), map.as_mut_ptr() as _)
-       .expect("Could not map buffer");
+    // SAFETY: `fd` is the dma-buf of the dumb buffer, `map` covers all of it and outlives `buffer`
+    let mut buffer = unsafe {
+        Buffer::map_dmabuf(fd.into_raw_fd(), 0, buffer_width, buffer_height, buffer_pitch, Format::RGBA8888, map.as_mut_ptr() as _)
+    }.expect("Could not map buffer");
     // let mut buffer = Buffer::allocate(640, 480, Format::RGBA8888).unwrap();
     for i in 0..1000 {
         buffer.clear(None, Color { r: 0, g: 0, b: 0, a: 255 }).unwrap();
@@ -1,5 +1,3 @@
-use std::io::Write;
-
 use vglite_rs::{Context, Buffer, Color, Format, Rectangle};
 
 fn main() {
@@ -11,7 +9,6 @@ fn main() {
     println!("finish");
     // drop(buffer);
     // write buffer to file
-    let mut file = std::fs::File::create("test.raw").unwrap();
-    file.write_all(buffer.data()).unwrap();
+    buffer.save_png("test.png").unwrap();
     println!("write to file");
 }
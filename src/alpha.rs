@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// Alpha used for every pixel of the source or the destination
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalAlpha {
+    /// Alpha of the pixels
+    #[default]
+    Normal,
+    /// This alpha instead of the alpha of the pixels
+    Global(u8),
+    /// Alpha of the pixels multiplied by this one
+    Scaled(u8)
+}
+
+impl GlobalAlpha {
+    fn mode(&self) -> (vg_lite_global_alpha, u8) {
+        match *self {
+            Self::Normal => (vg_lite_global_alpha_VG_LITE_NORMAL, 0xff),
+            Self::Global(alpha) => (vg_lite_global_alpha_VG_LITE_GLOBAL, alpha),
+            Self::Scaled(alpha) => (vg_lite_global_alpha_VG_LITE_SCALED, alpha)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Side {
+    Source,
+    Dest
+}
+
+impl Side {
+    fn of<'s>(&self, state: &'s mut RenderState<'static>) -> &'s mut GlobalAlpha {
+        match self {
+            Side::Source => &mut state.source_alpha,
+            Side::Dest => &mut state.dest_alpha
+        }
+    }
+}
+
+pub(crate) fn apply(side: Side, alpha: GlobalAlpha) -> Result<(), Error> {
+    let (mode, value) = alpha.mode();
+    wrap_result(driver(|| unsafe {
+        match side {
+            Side::Source => vg_lite_source_global_alpha(mode, value),
+            Side::Dest => vg_lite_dest_global_alpha(mode, value)
+        }
+    }), ())
+}
+
+pub(crate) fn set(context: &Context, side: Side, alpha: GlobalAlpha) -> Result<(), Error> {
+    if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_GLOBAL_ALPHA) {
+        return Err(Error::NotSupport);
+    }
+    apply(side, alpha)?;
+    context.track(|state| *side.of(state) = alpha);
+    Ok(())
+}
+
+/// Global alpha in effect for the draws issued while it lives, the previous one is restored on drop
+#[must_use = "the global alpha is restored when the guard is dropped"]
+pub struct GlobalAlphaGuard<'a> {
+    side: Side,
+    previous: GlobalAlpha,
+    context: &'a Context
+}
+
+impl<'a> GlobalAlphaGuard<'a> {
+    fn new(context: &'a Context, side: Side, alpha: GlobalAlpha) -> Result<Self, Error> {
+        let previous = context.track(|state| *side.of(state));
+        set(context, side, alpha)?;
+        Ok(GlobalAlphaGuard { side, previous, context })
+    }
+
+    /// Source alpha in force faded by `opacity`, no guard is needed when fully opaque
+    pub(crate) fn opacity(context: &'a Context, opacity: u8) -> Result<Option<Self>, Error> {
+        if opacity == 0xff {
+            return Ok(None);
+        }
+        let fade = |alpha: u8| ((alpha as u32 * opacity as u32 + 127) / 255) as u8;
+        let alpha = match context.render_state().source_alpha {
+            GlobalAlpha::Normal => GlobalAlpha::Scaled(opacity),
+            GlobalAlpha::Global(alpha) => GlobalAlpha::Global(fade(alpha)),
+            GlobalAlpha::Scaled(alpha) => GlobalAlpha::Scaled(fade(alpha))
+        };
+        Self::new(context, Side::Source, alpha).map(Some)
+    }
+}
+
+impl Drop for GlobalAlphaGuard<'_> {
+    fn drop(&mut self) {
+        let _ = set(self.context, self.side, self.previous);
+    }
+}
+
+impl Context {
+    /// Alpha of the sources of the next blits and pattern draws
+    pub fn source_global_alpha(&self, alpha: GlobalAlpha) -> Result<GlobalAlphaGuard<'_>, Error> {
+        GlobalAlphaGuard::new(self, Side::Source, alpha)
+    }
+
+    /// Alpha of the target pixels the next draws blend with
+    pub fn dest_global_alpha(&self, alpha: GlobalAlpha) -> Result<GlobalAlphaGuard<'_>, Error> {
+        GlobalAlphaGuard::new(self, Side::Dest, alpha)
+    }
+}
@@ -0,0 +1,272 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Place of one image inside an [`Atlas`], padding excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AtlasHandle {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl AtlasHandle {
+    pub fn rect(&self) -> Rectangle {
+        Rectangle { x: self.x as i32, y: self.y as i32, width: self.width as i32, height: self.height as i32 }
+    }
+}
+
+/// How the pixels of an [`Atlas`] are spent, in pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasUsage {
+    /// Covered by images
+    pub used: u32,
+    /// Lost under the skyline to padding, alignment and gaps no image can reach anymore
+    pub wasted: u32,
+    /// Still available above the skyline
+    pub free: u32
+}
+
+/// Bottom-left skyline packer: the top edge of the packed images, as `(x, y, width)` segments
+#[derive(Debug, Clone)]
+struct Skyline {
+    width: u32,
+    height: u32,
+    segments: Vec<(u32, u32, u32)>
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Skyline { width, height, segments: vec![(0, 0, width)] }
+    }
+
+    /// Lowest then leftmost position fitting `width * height`
+    fn find(&self, width: u32, height: u32) -> Option<(usize, u32, u32)> {
+        let mut best: Option<(usize, u32, u32)> = None;
+        for (i, &(x, _, _)) in self.segments.iter().enumerate() {
+            if x + width > self.width {
+                break;
+            }
+            // highest segment under the span
+            let mut y = 0;
+            let mut covered = 0;
+            for &(_, top, w) in &self.segments[i..] {
+                y = y.max(top);
+                covered += w;
+                if covered >= width {
+                    break;
+                }
+            }
+            if y + height > self.height {
+                continue;
+            }
+            if best.is_none_or(|(_, _, best_y)| y < best_y) {
+                best = Some((i, x, y));
+            }
+        }
+        best
+    }
+
+    fn place(&mut self, index: usize, x: u32, y: u32, width: u32, height: u32) {
+        self.segments.insert(index, (x, y + height, width));
+        let end = x + width;
+        // shrink or drop the segments now under the new one
+        let i = index + 1;
+        while i < self.segments.len() {
+            let (sx, sy, sw) = self.segments[i];
+            if sx >= end {
+                break;
+            }
+            if sx + sw <= end {
+                self.segments.remove(i);
+            } else {
+                self.segments[i] = (end, sy, sx + sw - end);
+                break;
+            }
+        }
+        self.segments.dedup_by(|next, prev| {
+            if prev.1 == next.1 {
+                prev.2 += next.2;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Pixels under the skyline
+    fn covered(&self) -> u32 {
+        self.segments.iter().map(|&(_, y, w)| y * w).sum()
+    }
+}
+
+/// Many small images packed into one GPU buffer, blitted by name with [`Buffer::blit_rect`]
+pub struct Atlas {
+    buffer: Buffer<'static>,
+    skyline: Skyline,
+    entries: HashMap<String, AtlasHandle>,
+    padding: u32,
+    /// Horizontal alignment of every image, in pixels
+    align: u32
+}
+
+/// Layout of the images inside an [`Atlas`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasOptions {
+    /// Empty pixels kept on the right and bottom of each image, avoids bleeding with bilinear filtering
+    pub padding: u32,
+    /// Start every image on [`ADDRESS_ALIGNMENT`] bytes so that [`Atlas::region`] works for all of them
+    pub aligned: bool
+}
+
+/// Collects images and packs them all at once, tallest first, see [`Atlas::insert`] to add more later
+pub struct AtlasBuilder {
+    width: u32,
+    height: u32,
+    format: Format,
+    options: AtlasOptions,
+    images: Vec<(String, Image)>
+}
+
+impl AtlasBuilder {
+    pub fn new(width: u32, height: u32, format: Format) -> Self {
+        AtlasBuilder { width, height, format, options: AtlasOptions::default(), images: Vec::new() }
+    }
+
+    /// See [`AtlasOptions::padding`]
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.options.padding = padding;
+        self
+    }
+
+    /// See [`AtlasOptions::aligned`]
+    pub fn aligned(mut self, aligned: bool) -> Self {
+        self.options.aligned = aligned;
+        self
+    }
+
+    pub fn add<S: Into<String>>(mut self, name: S, image: Image) -> Self {
+        self.images.push((name.into(), image));
+        self
+    }
+
+    /// Decode PNG, BMP or PPM bytes, see [`Image::decode`]
+    pub fn add_encoded<S: Into<String>>(self, name: S, bytes: &[u8]) -> Result<Self, Error> {
+        Ok(self.add(name, Image::decode(bytes)?))
+    }
+
+    /// Raw pixels in any format understood by [`convert`]
+    pub fn add_raw<'a, S: Into<String>, P: Into<PixelSlice<'a>>>(self, name: S, pixels: P) -> Result<Self, Error> {
+        let pixels = pixels.into();
+        let rgba = convert(pixels, Format::RGBA8888, ConvertOptions::default())?;
+        let colors = rgba.chunks_exact(4).map(|c| Color::rgba(c[0], c[1], c[2], c[3])).collect();
+        Ok(self.add(name, Image::new(pixels.width(), pixels.height(), colors)?))
+    }
+
+    /// Allocate the buffer, pack every image and upload them in one go
+    pub fn build(mut self) -> Result<Atlas, Error> {
+        let mut atlas = Atlas::new(self.width, self.height, self.format, self.options)?;
+        self.images.sort_by_key(|(_, image)| std::cmp::Reverse((image.height(), image.width())));
+        let stride = atlas.buffer.stride() as usize;
+        let mut staging = vec![0; stride * self.height as usize];
+        for (name, image) in &self.images {
+            let handle = atlas.reserve(name, image)?;
+            copy_image(&mut staging, stride, handle, image, self.format);
+        }
+        atlas.buffer.upload(&staging, stride as u32)?;
+        Ok(atlas)
+    }
+}
+
+/// Write `image` packed in `format` at the place of `handle`
+fn copy_image(data: &mut [u8], stride: usize, handle: AtlasHandle, image: &Image, format: Format) {
+    let bpp = format.bpp() as usize;
+    let row = image.width() as usize * bpp;
+    let pixels = image.pack(format);
+    for (y, src) in pixels.chunks_exact(row.max(1)).enumerate().take(image.height() as usize) {
+        let start = (handle.y as usize + y) * stride + handle.x as usize * bpp;
+        data[start..start + row].copy_from_slice(src);
+    }
+}
+
+impl Atlas {
+    /// Empty atlas, filled with [`Atlas::insert`]
+    pub fn new(width: u32, height: u32, format: Format, options: AtlasOptions) -> Result<Self, Error> {
+        if format.is_yuv() || format.is_indexed() {
+            return Err(Error::NotSupport);
+        }
+        let buffer = Buffer::allocate(width, height, format)?;
+        buffer.data()?.fill(0);
+        // smallest step in pixels landing on the alignment, 64 pixels for 3 bytes formats
+        let align = match options.aligned {
+            true => (1..=ADDRESS_ALIGNMENT).find(|px| (px * format.bpp()).is_multiple_of(ADDRESS_ALIGNMENT)).unwrap(),
+            false => 1
+        };
+        Ok(Atlas { buffer, skyline: Skyline::new(width, height), entries: HashMap::new(), padding: options.padding, align })
+    }
+
+    fn reserve(&mut self, name: &str, image: &Image) -> Result<AtlasHandle, Error> {
+        if self.entries.contains_key(name) {
+            return Err(Error::AlreadyExists);
+        }
+        let width = (image.width() + self.padding).next_multiple_of(self.align);
+        let height = image.height() + self.padding;
+        let (index, x, y) = self.skyline.find(width, height).ok_or(Error::OutOfResource)?;
+        self.skyline.place(index, x, y, width, height);
+        let handle = AtlasHandle { x, y, width: image.width(), height: image.height() };
+        self.entries.insert(name.to_string(), handle);
+        Ok(handle)
+    }
+
+    /// Pack one more image and copy it in place with the CPU, call [`Context::finish`] first
+    /// if the GPU may still be reading the atlas
+    pub fn insert<S: AsRef<str>>(&mut self, name: S, image: &Image) -> Result<AtlasHandle, Error> {
+        let handle = self.reserve(name.as_ref(), image)?;
+        let stride = self.buffer.stride() as usize;
+        copy_image(self.buffer.data()?, stride, handle, image, self.buffer.format());
+        Ok(handle)
+    }
+
+    pub fn get(&self, name: &str) -> Option<AtlasHandle> {
+        self.entries.get(name).copied()
+    }
+
+    /// Every packed image by name
+    pub fn entries(&self) -> impl Iterator<Item = (&str, AtlasHandle)> {
+        self.entries.iter().map(|(name, handle)| (name.as_str(), *handle))
+    }
+
+    pub fn usage(&self) -> AtlasUsage {
+        let used = self.entries.values().map(|h| h.width * h.height).sum();
+        let covered = self.skyline.covered();
+        AtlasUsage { used, wasted: covered - used, free: self.skyline.width * self.skyline.height - covered }
+    }
+
+    pub fn buffer(&self) -> &Buffer<'static> {
+        &self.buffer
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer<'static> {
+        &mut self.buffer
+    }
+
+    /// View of one image, see [`Buffer::region`], only reliable for [`AtlasOptions::aligned`] atlases
+    pub fn region(&mut self, handle: AtlasHandle) -> Result<BufferView<'_>, Error> {
+        self.buffer.region(handle.rect())
+    }
+
+    /// Blit one image to `target`, its top left corner lands on the origin of `matrix`
+    pub fn blit(
+        &mut self,
+        target: &mut Buffer,
+        handle: AtlasHandle,
+        matrix: &mut Transform,
+        blend: Blend,
+        color: Color,
+        filter: Filter
+    ) -> Result<(), Error> {
+        target.blit_rect(&mut self.buffer, &mut handle.rect(), matrix, blend, color, filter)
+    }
+}
@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// Largest sigma a 3x3 kernel samples well, see [`GaussianWeights::from_sigma`]
+const MAX_SIGMA: f32 = 0.8;
+
+/// Weights of the 3x3 kernel of [`Filter::Gaussian`]: `w0` for the center, `w1` for the
+/// four edge neighbours and `w2` for the corners. `w0 + 4 * w1 + 4 * w2` must be 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaussianWeights {
+    pub w0: f32,
+    pub w1: f32,
+    pub w2: f32
+}
+
+impl GaussianWeights {
+    /// Gaussian of standard deviation `sigma` sampled on the kernel, a 3x3 kernel can't
+    /// go much past a sigma of 0.8 and tends to a box filter above
+    pub fn from_sigma(sigma: f32) -> Self {
+        if sigma <= 0. {
+            return GaussianWeights { w0: 1., w1: 0., w2: 0. };
+        }
+        let g = |d2: f32| (-d2 / (2. * sigma * sigma)).exp();
+        let (w1, w2) = (g(1.), g(2.));
+        let sum = 1. + 4. * w1 + 4. * w2;
+        GaussianWeights { w0: 1. / sum, w1: w1 / sum, w2: w2 / sum }
+    }
+
+    /// Kernel adding `variance` pixels² per pass, up to what a sigma of [`MAX_SIGMA`] adds
+    fn from_variance(variance: f32) -> Self {
+        let (mut low, mut high) = (0., MAX_SIGMA);
+        for _ in 0..24 {
+            let sigma = (low + high) / 2.;
+            match Self::from_sigma(sigma).variance() < variance {
+                true => low = sigma,
+                false => high = sigma
+            }
+        }
+        Self::from_sigma(high)
+    }
+
+    /// Variance along one axis added by one pass, in pixels²
+    pub fn variance(&self) -> f32 {
+        2. * (self.w1 + 2. * self.w2)
+    }
+}
+
+impl Default for GaussianWeights {
+    /// The 1-2-1 binomial kernel
+    fn default() -> Self {
+        GaussianWeights { w0: 0.25, w1: 0.125, w2: 0.0625 }
+    }
+}
+
+impl Filter {
+    /// Load the kernel of [`Filter::Gaussian`] before a draw using this filter
+    pub(crate) fn bind(self) -> Result<vg_lite_filter, Error> {
+        if let Filter::Gaussian(weights) = self {
+            if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_GAUSSIAN_BLUR) {
+                return Err(Error::NotSupport);
+            }
+            wrap_result(driver(|| unsafe { vg_lite_gaussian_filter(weights.w0, weights.w1, weights.w2) }), ())?;
+        }
+        Ok(self.into())
+    }
+}
+
+/// Blur `buffer` in place with a gaussian of standard deviation `radius` pixels, e.g. behind a
+/// frosted glass panel. Returns once the GPU is done.
+///
+/// Large radii are approximated on a downsampled copy, each halving of the resolution doubles
+/// the reach of a 3x3 pass, so that at most 9 passes are needed before upsampling back.
+pub fn blur(buffer: &mut Buffer, radius: f32) -> Result<(), Error> {
+    if buffer.format().is_yuv() || buffer.format().is_indexed() {
+        return Err(Error::NotSupport);
+    }
+    if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_GAUSSIAN_BLUR) {
+        return Err(Error::NotSupport);
+    }
+    if radius <= 0. {
+        return Ok(());
+    }
+    let result = blur_passes(buffer, radius);
+    // the scratch buffers are gone once this returns
+    inflight::finish()?;
+    result
+}
+
+fn blur_passes(buffer: &mut Buffer, radius: f32) -> Result<(), Error> {
+    let format = buffer.format();
+    let mut scale = 1;
+    let mut front = None;
+    // one halving at a time, a single bilinear blit would skip pixels
+    while radius / scale as f32 > 2. && buffer.width() >= scale * 2 && buffer.height() >= scale * 2 {
+        scale *= 2;
+        let mut half = Buffer::allocate(buffer.width().div_ceil(scale), buffer.height().div_ceil(scale), format)?;
+        let mut matrix = Transform::default();
+        matrix.scale(0.5, 0.5);
+        match front.as_mut() {
+            Some(front) => half.blit(front, &mut matrix, Blend::None, Color::WHITE, Filter::Bilinear)?,
+            None => half.blit(buffer, &mut matrix, Blend::None, Color::WHITE, Filter::Bilinear)?
+        }
+        front = Some(half);
+    }
+    let mut front = match front {
+        Some(front) => front,
+        None => {
+            let mut copy = Buffer::allocate(buffer.width(), buffer.height(), format)?;
+            copy.blit(buffer, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer)?;
+            copy
+        }
+    };
+    let mut back = Buffer::allocate(front.width(), front.height(), format)?;
+    // spread the variance left at this resolution evenly over the passes
+    let variance = (radius / scale as f32).powi(2);
+    let passes = (variance / GaussianWeights::from_sigma(MAX_SIGMA).variance()).ceil().max(1.);
+    let weights = GaussianWeights::from_variance(variance / passes);
+    for _ in 0..passes as u32 {
+        back.blit(&mut front, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Gaussian(weights))?;
+        std::mem::swap(&mut front, &mut back);
+    }
+    let (up, resample) = match scale {
+        1 => (1., Filter::Pointer),
+        _ => (scale as f32, Filter::Bilinear)
+    };
+    buffer.blit(&mut front, Transform::default().scale(up, up), Blend::None, Color::WHITE, resample)
+}
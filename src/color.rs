@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::str::FromStr;
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Straight (non-premultiplied) 8-bit RGBA color.
+///
+/// The driver takes colors as `0xAABBGGRR` and converts them to the layout of the
+/// target buffer itself, use [`Color::pack`] to get the bits as they are stored in memory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// Channels in `0.0..=1.0`, out of range values are clamped
+    pub fn from_f32(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r: unit_to_u8(r), g: unit_to_u8(g), b: unit_to_u8(b), a: unit_to_u8(a) }
+    }
+
+    /// `[r, g, b, a]` in `0.0..=1.0`
+    pub fn to_f32(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| c as f32 / 255.)
+    }
+
+    /// Parse `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, the leading `#` is optional
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.is_ascii() {
+            return Err(Error::InvalidArgument);
+        }
+        let digit = |i: usize, n: usize| u8::from_str_radix(&hex[i..i + n], 16)
+            .map_err(|_| Error::InvalidArgument);
+        match hex.len() {
+            3 | 4 => {
+                let short = |i| digit(i, 1).map(|c| c * 0x11);
+                let a = if hex.len() == 4 { short(3)? } else { 255 };
+                Ok(Color::rgba(short(0)?, short(1)?, short(2)?, a))
+            }
+            6 | 8 => {
+                let a = if hex.len() == 8 { digit(6, 2)? } else { 255 };
+                Ok(Color::rgba(digit(0, 2)?, digit(2, 2)?, digit(4, 2)?, a))
+            }
+            _ => Err(Error::InvalidArgument)
+        }
+    }
+
+    /// Multiply color channels by alpha
+    pub fn premultiply(self) -> Self {
+        let mul = |c: u8| ((c as u32 * self.a as u32 + 127) / 255) as u8;
+        Color { r: mul(self.r), g: mul(self.g), b: mul(self.b), a: self.a }
+    }
+
+    /// Inverse of [`Color::premultiply`], fully transparent colors become [`Color::TRANSPARENT`]
+    pub fn unpremultiply(self) -> Self {
+        if self.a == 0 {
+            return Color::TRANSPARENT;
+        }
+        let div = |c: u8| ((c as u32 * 255 + self.a as u32 / 2) / self.a as u32).min(255) as u8;
+        Color { r: div(self.r), g: div(self.g), b: div(self.b), a: self.a }
+    }
+
+    /// Pixel bits of this color as stored in a buffer of `format`, little endian
+    pub fn pack(self, format: Format) -> u32 {
+        let layout = format.layout();
+        if layout.luminance {
+            return self.luminance() as u32;
+        }
+        [self.r, self.g, self.b, self.a]
+            .iter()
+            .zip(layout.channels)
+            .filter(|(_, (_, width))| *width > 0)
+            .fold(0, |pixel, (&c, (shift, width))| pixel | ((c as u32) >> (8 - width)) << shift)
+    }
+
+    /// Inverse of [`Color::pack`], missing channels are filled with 0, or 255 for alpha
+    pub fn unpack(pixel: u32, format: Format) -> Self {
+        let layout = format.layout();
+        let [r, g, b, a] = layout.channels.map(|(shift, width)| {
+            if width == 0 {
+                return 0;
+            }
+            expand((pixel >> shift) & ((1 << width) - 1), width)
+        });
+        let a = if layout.channels[3].1 == 0 { 255 } else { a };
+        Color { r, g, b, a }
+    }
+
+    /// BT.709 luma, as used by the driver for [`Format::L8`]
+    pub fn luminance(self) -> u8 {
+        ((self.r as u32 * 54 + self.g as u32 * 183 + self.b as u32 * 19 + 128) >> 8) as u8
+    }
+}
+
+/// Scale a `width` bits channel to 8 bits by replicating its high bits, so that the maximum maps to 255
+pub(crate) fn expand(c: u32, width: u32) -> u8 {
+    let mut wide = 0;
+    let mut filled = 0;
+    while filled < 8 {
+        wide |= (c << 8) >> (width + filled);
+        filled += width;
+    }
+    (wide & 0xff) as u8
+}
+
+fn unit_to_u8(c: f32) -> u8 {
+    (c.clamp(0., 1.) * 255. + 0.5) as u8
+}
+
+impl From<Color> for vg_lite_color_t {
+    /// `0xAABBGGRR`, the channel order expected by the driver for every target format
+    fn from(color: Color) -> Self {
+        ((color.a as u32) << 24) |
+        ((color.b as u32) << 16) |
+        ((color.g as u32) << 8) |
+        (color.r as u32)
+    }
+}
+
+impl From<[f32; 4]> for Color {
+    fn from([r, g, b, a]: [f32; 4]) -> Self {
+        Color::from_f32(r, g, b, a)
+    }
+}
+
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("transparent", Color::TRANSPARENT),
+    ("black", Color::rgb(0, 0, 0)),
+    ("silver", Color::rgb(192, 192, 192)),
+    ("gray", Color::rgb(128, 128, 128)),
+    ("grey", Color::rgb(128, 128, 128)),
+    ("white", Color::rgb(255, 255, 255)),
+    ("maroon", Color::rgb(128, 0, 0)),
+    ("red", Color::rgb(255, 0, 0)),
+    ("purple", Color::rgb(128, 0, 128)),
+    ("fuchsia", Color::rgb(255, 0, 255)),
+    ("magenta", Color::rgb(255, 0, 255)),
+    ("green", Color::rgb(0, 128, 0)),
+    ("lime", Color::rgb(0, 255, 0)),
+    ("olive", Color::rgb(128, 128, 0)),
+    ("yellow", Color::rgb(255, 255, 0)),
+    ("navy", Color::rgb(0, 0, 128)),
+    ("blue", Color::rgb(0, 0, 255)),
+    ("teal", Color::rgb(0, 128, 128)),
+    ("aqua", Color::rgb(0, 255, 255)),
+    ("cyan", Color::rgb(0, 255, 255)),
+    ("orange", Color::rgb(255, 165, 0)),
+];
+
+/// `rgb()` / `rgba()` component, either `0..=255` or a percentage
+fn parse_css_channel(s: &str) -> Result<u8, Error> {
+    let s = s.trim();
+    match s.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f32>().map(|p| unit_to_u8(p / 100.)),
+        None => s.parse::<f32>().map(|c| c.clamp(0., 255.).round() as u8)
+    }.map_err(|_| Error::InvalidArgument)
+}
+
+/// Alpha component, either `0.0..=1.0` or a percentage
+fn parse_css_alpha(s: &str) -> Result<u8, Error> {
+    let s = s.trim();
+    match s.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f32>().map(|p| unit_to_u8(p / 100.)),
+        None => s.parse::<f32>().map(unit_to_u8)
+    }.map_err(|_| Error::InvalidArgument)
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// CSS color: hex notation, `rgb()`, `rgba()` or a basic color keyword
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with('#') {
+            return Color::from_hex(s);
+        }
+        let lower = s.to_ascii_lowercase();
+        if let Some(args) = lower.strip_prefix("rgba(").or_else(|| lower.strip_prefix("rgb(")) {
+            let args = args.strip_suffix(')').ok_or(Error::InvalidArgument)?;
+            // both `rgb(1, 2, 3, 0.5)` and `rgb(1 2 3 / 0.5)` are accepted
+            let (channels, alpha) = match args.split_once('/') {
+                Some((channels, alpha)) => (channels.replace(',', " "), Some(alpha.trim())),
+                None => (args.replace(',', " "), None)
+            };
+            let parts: Vec<&str> = channels.split_whitespace().collect();
+            let (rgb, alpha) = match (parts.as_slice(), alpha) {
+                ([r, g, b], alpha) => ([*r, *g, *b], alpha),
+                ([r, g, b, a], None) => ([*r, *g, *b], Some(*a)),
+                _ => return Err(Error::InvalidArgument)
+            };
+            return Ok(Color::rgba(
+                parse_css_channel(rgb[0])?,
+                parse_css_channel(rgb[1])?,
+                parse_css_channel(rgb[2])?,
+                alpha.map(parse_css_alpha).transpose()?.unwrap_or(255)
+            ));
+        }
+        NAMED_COLORS.iter()
+            .find(|(name, _)| *name == lower)
+            .map(|(_, color)| *color)
+            .ok_or(Error::InvalidArgument)
+    }
+}
@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// Source colors between `low` and `high`, inclusive on every channel, get `alpha`.
+/// Alpha of `low` and `high` is ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorKeyRange {
+    pub low: Color,
+    pub high: Color,
+    pub alpha: u8,
+    pub enabled: bool
+}
+
+impl ColorKeyRange {
+    pub fn contains(&self, color: Color) -> bool {
+        (self.low.r..=self.high.r).contains(&color.r)
+            && (self.low.g..=self.high.g).contains(&color.g)
+            && (self.low.b..=self.high.b).contains(&color.b)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.low.r <= self.high.r && self.low.g <= self.high.g && self.low.b <= self.high.b
+    }
+}
+
+impl From<ColorKeyRange> for vg_lite_color_key {
+    fn from(range: ColorKeyRange) -> Self {
+        vg_lite_color_key {
+            enable: range.enabled as u8,
+            low_r: range.low.r,
+            low_g: range.low.g,
+            low_b: range.low.b,
+            alpha: range.alpha,
+            hign_r: range.high.r,
+            hign_g: range.high.g,
+            hign_b: range.high.b
+        }
+    }
+}
+
+/// Up to four color ranges replacing the alpha of source pixels, e.g. a magenta key
+/// for bitmaps without alpha. Ranges added first take priority where they overlap
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ColorKey {
+    ranges: Vec<ColorKeyRange>
+}
+
+impl ColorKey {
+    pub const MAX_RANGES: usize = 4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn range(mut self, low: Color, high: Color, alpha: u8) -> Self {
+        self.ranges.push(ColorKeyRange { low, high, alpha, enabled: true });
+        self
+    }
+
+    /// A single color, usually made fully transparent
+    pub fn color(self, color: Color, alpha: u8) -> Self {
+        self.range(color, color, alpha)
+    }
+
+    /// Keep the range at `index` in its priority slot while turning it on or off
+    pub fn enable(mut self, index: usize, enabled: bool) -> Self {
+        if let Some(range) = self.ranges.get_mut(index) {
+            range.enabled = enabled;
+        }
+        self
+    }
+
+    pub fn ranges(&self) -> &[ColorKeyRange] {
+        &self.ranges
+    }
+
+    /// At most [`ColorKey::MAX_RANGES`] ranges, each with `low <= high` on every channel
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.ranges.len() > Self::MAX_RANGES || !self.ranges.iter().all(ColorKeyRange::is_valid) {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Alpha the GPU gives to a source pixel of `color`, `None` if no enabled range matches
+    pub fn alpha_for(&self, color: Color) -> Option<u8> {
+        self.ranges.iter()
+            .find(|range| range.enabled && range.contains(color))
+            .map(|range| range.alpha)
+    }
+
+    /// Ranges in priority order, unused slots disabled
+    fn raw(&self) -> [vg_lite_color_key; 4] {
+        let disabled = ColorKeyRange { low: Color::BLACK, high: Color::BLACK, alpha: 0, enabled: false };
+        std::array::from_fn(|i| self.ranges.get(i).copied().unwrap_or(disabled).into())
+    }
+}
+
+pub(crate) fn apply(key: &ColorKey) -> Result<(), Error> {
+    let mut raw = key.raw();
+    wrap_result(driver(|| unsafe { vg_lite_set_color_key(raw.as_mut_ptr()) }), ())
+}
+
+pub(crate) fn set(context: &Context, key: &ColorKey) -> Result<(), Error> {
+    if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_COLOR_KEY) {
+        return Err(Error::NotSupport);
+    }
+    key.validate()?;
+    apply(key)?;
+    context.track(|state| state.color_key = key.clone());
+    Ok(())
+}
+
+/// Color key in effect for the blits issued while it lives, the previous one is restored on drop
+#[must_use = "the color key is restored when the guard is dropped"]
+pub struct ColorKeyGuard<'a> {
+    context: &'a Context,
+    previous: ColorKey
+}
+
+impl Drop for ColorKeyGuard<'_> {
+    fn drop(&mut self) {
+        let _ = set(self.context, &self.previous);
+    }
+}
+
+impl Context {
+    pub fn color_key(&self, key: &ColorKey) -> Result<ColorKeyGuard<'_>, Error> {
+        let previous = self.track(|state| state.color_key.clone());
+        set(self, key)?;
+        Ok(ColorKeyGuard { context: self, previous })
+    }
+}
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::ops::Mul;
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Channels a [`ColorMatrix`] writes, the others keep the source value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelChannels {
+    pub r: bool,
+    pub g: bool,
+    pub b: bool,
+    pub a: bool
+}
+
+impl PixelChannels {
+    pub const ALL: PixelChannels = PixelChannels { r: true, g: true, b: true, a: true };
+    pub const RGB: PixelChannels = PixelChannels { r: true, g: true, b: true, a: false };
+    pub const NONE: PixelChannels = PixelChannels { r: false, g: false, b: false, a: false };
+}
+
+impl Default for PixelChannels {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl From<PixelChannels> for vg_lite_pixel_channel_enable {
+    fn from(channels: PixelChannels) -> Self {
+        vg_lite_pixel_channel_enable {
+            enable_a: channels.a as u8,
+            enable_b: channels.b as u8,
+            enable_g: channels.g as u8,
+            enable_r: channels.r as u8
+        }
+    }
+}
+
+/// 4x5 matrix transforming the color of source pixels, as SVG's `feColorMatrix`: rows give
+/// r, g, b, a from the r, g, b, a columns plus an offset, all in `0.0..=1.0` units.
+///
+/// `a * b` applies `b` first, then `a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix {
+    pub m: [[f32; 5]; 4]
+}
+
+/// Luminance weights of BT.709, as used by the CSS filter functions
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+impl ColorMatrix {
+    pub const IDENTITY: ColorMatrix = ColorMatrix { m: [
+        [1., 0., 0., 0., 0.],
+        [0., 1., 0., 0., 0.],
+        [0., 0., 1., 0., 0.],
+        [0., 0., 0., 1., 0.]
+    ]};
+
+    /// Scale and offset each of r, g, b; alpha is left alone
+    fn rgb(m: [[f32; 3]; 3], offset: f32) -> Self {
+        let row = |r: [f32; 3]| [r[0], r[1], r[2], 0., offset];
+        ColorMatrix { m: [row(m[0]), row(m[1]), row(m[2]), [0., 0., 0., 1., 0.]] }
+    }
+
+    pub fn grayscale() -> Self {
+        Self::saturation(0.)
+    }
+
+    pub fn sepia() -> Self {
+        Self::rgb([
+            [0.393, 0.769, 0.189],
+            [0.349, 0.686, 0.168],
+            [0.272, 0.534, 0.131]
+        ], 0.)
+    }
+
+    /// 0 is grayscale, 1 leaves the colors unchanged, above 1 oversaturates
+    pub fn saturation(s: f32) -> Self {
+        let [lr, lg, lb] = LUMA;
+        Self::rgb([
+            [lr + (1. - lr) * s, lg - lg * s, lb - lb * s],
+            [lr - lr * s, lg + (1. - lg) * s, lb - lb * s],
+            [lr - lr * s, lg - lg * s, lb + (1. - lb) * s]
+        ], 0.)
+    }
+
+    /// Rotate hues around the luminance axis, keeping the luminance
+    pub fn hue_rotate(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let [lr, lg, lb] = LUMA;
+        Self::rgb([
+            [lr + cos * (1. - lr) - sin * lr, lg - cos * lg - sin * lg, lb - cos * lb + sin * (1. - lb)],
+            [lr - cos * lr + sin * 0.143, lg + cos * (1. - lg) + sin * 0.140, lb - cos * lb - sin * 0.283],
+            [lr - cos * lr - sin * (1. - lr), lg - cos * lg + sin * lg, lb + cos * (1. - lb) + sin * lb]
+        ], 0.)
+    }
+
+    /// Multiply r, g, b by `b`: 0 is black, 1 leaves the colors unchanged
+    pub fn brightness(b: f32) -> Self {
+        Self::rgb([[b, 0., 0.], [0., b, 0.], [0., 0., b]], 0.)
+    }
+
+    /// Scale r, g, b around mid gray: 0 is gray, 1 leaves the colors unchanged
+    pub fn contrast(c: f32) -> Self {
+        Self::rgb([[c, 0., 0.], [0., c, 0.], [0., 0., c]], 0.5 - 0.5 * c)
+    }
+
+    /// `1 - c` on r, g and b
+    pub fn invert() -> Self {
+        Self::rgb([[-1., 0., 0.], [0., -1., 0.], [0., 0., -1.]], 1.)
+    }
+
+    /// Transform `color` on every channel, as the GPU does
+    pub fn apply(&self, color: Color) -> Color {
+        self.apply_channels(color, PixelChannels::ALL)
+    }
+
+    /// Transform `color`, the channels not in `channels` keep their value
+    pub fn apply_channels(&self, color: Color, channels: PixelChannels) -> Color {
+        let input = color.to_f32();
+        let out: [f32; 4] = std::array::from_fn(|row| {
+            let m = &self.m[row];
+            (0..4).map(|col| m[col] * input[col]).sum::<f32>() + m[4]
+        });
+        let pick = |enabled: bool, i: usize| if enabled { out[i] } else { input[i] };
+        Color::from_f32(pick(channels.r, 0), pick(channels.g, 1), pick(channels.b, 2), pick(channels.a, 3))
+    }
+
+    /// Rows in the a, r, g, b order of `vg_lite_set_pixel_matrix`, columns likewise
+    fn raw(&self) -> vg_lite_pixel_matrix_t {
+        const ORDER: [usize; 4] = [3, 0, 1, 2];
+        let mut raw = [0.; 20];
+        for (row, &from) in ORDER.iter().enumerate() {
+            for (col, &c) in ORDER.iter().enumerate() {
+                raw[row * 5 + col] = self.m[from][c];
+            }
+            raw[row * 5 + 4] = self.m[from][4];
+        }
+        raw
+    }
+}
+
+impl Default for ColorMatrix {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for ColorMatrix {
+    type Output = ColorMatrix;
+
+    /// Both taken as 5x5 matrices with a last row of `[0, 0, 0, 0, 1]`
+    fn mul(self, rhs: ColorMatrix) -> ColorMatrix {
+        let rhs = |k: usize, col: usize| match k {
+            4 => (col == 4) as u8 as f32,
+            _ => rhs.m[k][col]
+        };
+        let m = std::array::from_fn(|row| {
+            std::array::from_fn(|col| (0..5).map(|k| self.m[row][k] * rhs(k, col)).sum())
+        });
+        ColorMatrix { m }
+    }
+}
+
+/// `None` disables every channel
+pub(crate) fn apply(matrix: Option<(ColorMatrix, PixelChannels)>) -> Result<(), Error> {
+    let (matrix, channels) = matrix.unwrap_or((ColorMatrix::IDENTITY, PixelChannels::NONE));
+    let mut raw = matrix.raw();
+    let mut channels = channels.into();
+    wrap_result(driver(|| unsafe { vg_lite_set_pixel_matrix(raw.as_mut_ptr(), &mut channels) }), ())
+}
+
+pub(crate) fn set(context: &Context, matrix: Option<(ColorMatrix, PixelChannels)>) -> Result<(), Error> {
+    if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_PIXEL_MATRIX) {
+        return Err(Error::NotSupport);
+    }
+    apply(matrix)?;
+    context.track(|state| state.pixel_matrix = matrix);
+    Ok(())
+}
+
+/// Color matrix in effect for the blits issued while it lives, the previous one is restored on drop
+#[must_use = "the color matrix is restored when the guard is dropped"]
+pub struct PixelMatrixGuard<'a> {
+    context: &'a Context,
+    previous: Option<(ColorMatrix, PixelChannels)>
+}
+
+impl Drop for PixelMatrixGuard<'_> {
+    fn drop(&mut self) {
+        let _ = set(self.context, self.previous);
+    }
+}
+
+impl Context {
+    /// Transform the source colors of the next blits, only on `channels`
+    pub fn pixel_matrix(&self, matrix: &ColorMatrix, channels: PixelChannels) -> Result<PixelMatrixGuard<'_>, Error> {
+        let previous = self.track(|state| state.pixel_matrix);
+        set(self, Some((*matrix, channels)))?;
+        Ok(PixelMatrixGuard { context: self, previous })
+    }
+}
@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// `channel * scale + bias` on each channel of the source pixels, `[r, g, b, a]` in `0.0..=1.0` units.
+/// Cheaper than a [`ColorMatrix`] when channels don't mix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorTransform {
+    pub scale: [f32; 4],
+    pub bias: [f32; 4]
+}
+
+impl ColorTransform {
+    pub const IDENTITY: ColorTransform = ColorTransform { scale: [1.; 4], bias: [0.; 4] };
+
+    /// Multiply every channel by those of `color`, white leaves the colors unchanged
+    pub fn tint(color: Color) -> Self {
+        ColorTransform { scale: color.to_f32(), bias: [0.; 4] }
+    }
+
+    /// Multiply alpha by `alpha`, colors are left alone
+    pub fn alpha_multiply(alpha: f32) -> Self {
+        ColorTransform { scale: [1., 1., 1., alpha], ..Self::IDENTITY }
+    }
+
+    /// `from` at 0, `to` at 1, e.g. one frame of a theme transition
+    pub fn lerp(from: &ColorTransform, to: &ColorTransform, t: f32) -> Self {
+        let mix = |a: [f32; 4], b: [f32; 4]| std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t);
+        ColorTransform { scale: mix(from.scale, to.scale), bias: mix(from.bias, to.bias) }
+    }
+
+    /// Transform `color` as the GPU does
+    pub fn apply(&self, color: Color) -> Color {
+        let c = color.to_f32();
+        let [r, g, b, a] = std::array::from_fn(|i| c[i] * self.scale[i] + self.bias[i]);
+        Color::from_f32(r, g, b, a)
+    }
+}
+
+impl Default for ColorTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<&ColorTransform> for vg_lite_color_transform {
+    fn from(transform: &ColorTransform) -> Self {
+        let [r_scale, g_scale, b_scale, a_scale] = transform.scale;
+        let [r_bias, g_bias, b_bias, a_bias] = transform.bias;
+        vg_lite_color_transform { a_scale, a_bias, r_scale, r_bias, g_scale, g_bias, b_scale, b_bias }
+    }
+}
+
+/// `None` disables the transform
+pub(crate) fn apply(transform: Option<ColorTransform>) -> Result<(), Error> {
+    let mut values = (&transform.unwrap_or_default()).into();
+    wrap_result(driver(|| unsafe { vg_lite_set_color_transform(&mut values) }), ())?;
+    wrap_result(driver(|| unsafe {
+        match transform {
+            Some(_) => vg_lite_enable_color_transform(),
+            None => vg_lite_disable_color_transform()
+        }
+    }), ())
+}
+
+pub(crate) fn set(context: &Context, transform: Option<ColorTransform>) -> Result<(), Error> {
+    if !has_feature(vg_lite_feature_gcFEATURE_BIT_VG_COLOR_TRANSFORMATION) {
+        return Err(Error::NotSupport);
+    }
+    apply(transform)?;
+    context.track(|state| state.color_transform = transform);
+    Ok(())
+}
+
+/// Color transform in effect for the draws issued while it lives, the previous one is restored on drop
+#[must_use = "the color transform is restored when the guard is dropped"]
+pub struct ColorTransformGuard<'a> {
+    context: &'a Context,
+    previous: Option<ColorTransform>
+}
+
+impl ColorTransformGuard<'_> {
+    /// Change the transform while keeping the guard, for animations
+    pub fn set(&mut self, transform: &ColorTransform) -> Result<(), Error> {
+        set(self.context, Some(*transform))
+    }
+}
+
+impl Drop for ColorTransformGuard<'_> {
+    fn drop(&mut self) {
+        let _ = set(self.context, self.previous);
+    }
+}
+
+impl Context {
+    pub fn color_transform(&self, transform: &ColorTransform) -> Result<ColorTransformGuard<'_>, Error> {
+        let previous = self.track(|state| state.color_transform);
+        set(self, Some(*transform))?;
+        Ok(ColorTransformGuard { context: self, previous })
+    }
+}
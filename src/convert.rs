@@ -0,0 +1,148 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::color::expand;
+use crate::*;
+
+/// Pixels in CPU memory, rows are `stride` bytes apart
+#[derive(Debug, Clone, Copy)]
+pub struct PixelSlice<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: u32,
+    format: Format
+}
+
+impl<'a> PixelSlice<'a> {
+    /// Rows without padding
+    pub fn new(data: &'a [u8], width: u32, height: u32, format: Format) -> Result<Self, Error> {
+        Self::with_stride(data, width, height, width * format.bpp(), format)
+    }
+
+    pub fn with_stride(data: &'a [u8], width: u32, height: u32, stride: u32, format: Format) -> Result<Self, Error> {
+        let row = width as usize * format.bpp() as usize;
+        if (stride as usize) < row || (height > 0 && data.len() < stride as usize * (height as usize - 1) + row) {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(PixelSlice { data, width, height, stride, format })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn row(&self, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride as usize;
+        &self.data[start..start + self.width as usize * self.format.bpp() as usize]
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let bpp = self.format.bpp() as usize;
+        let mut bytes = [0; 4];
+        bytes[..bpp].copy_from_slice(&self.row(y)[x as usize * bpp..][..bpp]);
+        Color::unpack(u32::from_le_bytes(bytes), self.format)
+    }
+}
+
+impl<'a> TryFrom<&'a Buffer<'_>> for PixelSlice<'a> {
+    type Error = Error;
+
+    /// CPU view of the buffer memory, call [`Context::finish`] first
+    fn try_from(buffer: &'a Buffer<'_>) -> Result<Self, Error> {
+        Ok(PixelSlice {
+            data: buffer.data()?,
+            width: buffer.width(),
+            height: buffer.height(),
+            stride: buffer.stride(),
+            format: buffer.format()
+        })
+    }
+}
+
+/// What happens to color channels relative to alpha during a conversion
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Copy channels as they are
+    #[default]
+    Keep,
+    /// Source is straight, destination is premultiplied
+    Premultiply,
+    /// Source is premultiplied, destination is straight
+    Unpremultiply
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// Ordered dithering for channels narrower than 8 bits, avoids banding on gradients
+    pub dither: bool,
+    pub alpha: AlphaMode
+}
+
+/// 4x4 Bayer matrix
+const BAYER: [[u32; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5]
+];
+
+/// Round `c` to one of its two neighbouring `width` bits levels, picking the upper one
+/// when the remainder exceeds `threshold / 16`. Values exactly on a level never move
+fn dither_channel(c: u8, width: u32, threshold: u32) -> u8 {
+    if width == 0 || width >= 8 {
+        return c;
+    }
+    let mut q = c as u32 >> (8 - width);
+    if expand(q, width) > c {
+        q -= 1;
+    }
+    let (low, high) = (expand(q, width) as u32, expand(q + 1, width) as u32);
+    if q < (1 << width) - 1 && (c as u32 - low) * 16 > threshold * (high - low) {
+        high as u8
+    } else {
+        low as u8
+    }
+}
+
+/// Convert to `format`, the result has rows without padding. YUV and indexed formats aren't supported
+pub fn convert<'a, S: Into<PixelSlice<'a>>>(src: S, format: Format, options: ConvertOptions) -> Result<Vec<u8>, Error> {
+    let src = src.into();
+    if [src.format, format].iter().any(|f| f.is_yuv() || f.is_indexed()) {
+        return Err(Error::NotSupport);
+    }
+    let bpp = format.bpp() as usize;
+    let mut dst = Vec::with_capacity(src.width as usize * src.height as usize * bpp);
+    if src.format == format && options == ConvertOptions::default() {
+        (0..src.height).for_each(|y| dst.extend_from_slice(src.row(y)));
+        return Ok(dst);
+    }
+    let bits = format.channel_bits();
+    for y in 0..src.height {
+        for x in 0..src.width {
+            let mut color = src.pixel(x, y);
+            color = match options.alpha {
+                AlphaMode::Keep => color,
+                AlphaMode::Premultiply => color.premultiply(),
+                AlphaMode::Unpremultiply => color.unpremultiply()
+            };
+            if options.dither {
+                let threshold = BAYER[y as usize % 4][x as usize % 4];
+                color = Color {
+                    r: dither_channel(color.r, bits[0], threshold),
+                    g: dither_channel(color.g, bits[1], threshold),
+                    b: dither_channel(color.b, bits[2], threshold),
+                    a: dither_channel(color.a, bits[3], threshold)
+                };
+            }
+            dst.extend_from_slice(&color.pack(format).to_le_bytes()[..bpp]);
+        }
+    }
+    Ok(dst)
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::vg_lite::*;
+use crate::*;
+
+#[derive(Default)]
+struct Signal {
+    result: Option<Result<(), Error>>,
+    waker: Option<Waker>
+}
+
+#[derive(Default)]
+struct FenceState {
+    /// Submission closed by the flush, see [`Context::in_flight`]
+    submission: u64,
+    signal: Mutex<Signal>,
+    done: Condvar
+}
+
+impl FenceState {
+    fn lock(&self) -> MutexGuard<'_, Signal> {
+        self.signal.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn complete(&self, result: Result<(), Error>) {
+        let mut signal = self.lock();
+        signal.result = Some(result);
+        if let Some(waker) = signal.waker.take() {
+            waker.wake();
+        }
+        self.done.notify_all();
+    }
+}
+
+/// Completion of the drawing submitted by one [`Context::flush`]. A helper thread polls the GPU
+/// until it is idle, the drawing thread keeps recording meanwhile
+#[derive(Clone)]
+pub struct Fence {
+    state: Arc<FenceState>
+}
+
+impl Fence {
+    /// `None` while the GPU is still busy
+    pub fn check(&self) -> Option<Result<(), Error>> {
+        self.state.lock().result
+    }
+
+    pub fn wait(&self) -> Result<(), Error> {
+        let mut signal = self.state.lock();
+        loop {
+            if let Some(result) = signal.result {
+                return result;
+            }
+            signal = self.state.done.wait(signal).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Like [`Fence::wait`], fails with [`Error::Timeout`] if the GPU is not done by then
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        let mut signal = self.state.lock();
+        loop {
+            if let Some(result) = signal.result {
+                return result;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(Error::Timeout);
+            }
+            signal = self.state.done.wait_timeout(signal, left)
+                .unwrap_or_else(|poisoned| poisoned.into_inner()).0;
+        }
+    }
+}
+
+impl Future for Fence {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut signal = self.state.lock();
+        match signal.result {
+            Some(result) => Poll::Ready(result),
+            None => {
+                signal.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Idle state register of the GPU and the bits all set once every module is idle, as checked
+/// by the kernel driver before it signals a finish
+const IDLE_REGISTER: u32 = 0x4;
+const IDLE_STATE: u32 = 0x0B05;
+
+/// Time between two reads of [`IDLE_REGISTER`]
+const POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+/// Whether the GPU is done with everything flushed so far, the driver is only held for the read
+fn idle() -> Result<bool, Error> {
+    let mut state = 0;
+    wrap_result(driver(|| unsafe { vg_lite_get_register(IDLE_REGISTER, &mut state) }), ())?;
+    Ok(state & IDLE_STATE == IDLE_STATE)
+}
+
+/// Thread polling the GPU on behalf of the fences, `vg_lite_finish` would hold the driver
+/// for the whole wait
+pub(crate) struct FenceWorker {
+    sender: Sender<Arc<FenceState>>,
+    thread: JoinHandle<()>
+}
+
+impl FenceWorker {
+    fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel::<Arc<FenceState>>();
+        let thread = std::thread::spawn(move || {
+            while let Ok(first) = receiver.recv() {
+                // an idle GPU is done with every flush that returned before the read
+                let mut pending = vec![first];
+                let result = loop {
+                    pending.extend(receiver.try_iter());
+                    match idle() {
+                        Ok(false) => std::thread::sleep(POLL_INTERVAL),
+                        result => break result.map(|_| ())
+                    }
+                };
+                if result.is_ok() {
+                    pending.iter().for_each(|fence| inflight::complete(fence.submission));
+                }
+                pending.iter().for_each(|fence| fence.complete(result));
+            }
+        });
+        FenceWorker { sender, thread }
+    }
+}
+
+impl Context {
+    /// Fence signaled once the GPU is done with `submission` and everything before it
+    pub(crate) fn fence(&self, submission: u64) -> Fence {
+        let state = Arc::new(FenceState { submission, ..FenceState::default() });
+        let mut worker = self.fences.borrow_mut();
+        let worker = worker.get_or_insert_with(FenceWorker::spawn);
+        if worker.sender.send(state.clone()).is_err() {
+            state.complete(Err(Error::NoContext));
+        }
+        Fence { state }
+    }
+
+    /// Wait for the pending fences and stop their thread, before the driver is closed
+    pub(crate) fn stop_fences(&self) {
+        if let Some(worker) = self.fences.borrow_mut().take() {
+            drop(worker.sender);
+            let _ = worker.thread.join();
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+
+/// Pixel formats, channel names are listed from the least significant bits,
+/// e.g. [`Format::BGR565`] is blue in bits `0..5` and red in bits `11..16`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    RGBA8888,
+    BGRA8888,
+    RGB565,
+    BGR565,
+    RGBX8888,
+    BGRX8888,
+    ARGB8888,
+    ABGR8888,
+    XRGB8888,
+    XBGR8888,
+    RGB888,
+    BGR888,
+    RGBA4444,
+    BGRA4444,
+    ARGB4444,
+    ABGR4444,
+    RGBA5551,
+    BGRA5551,
+    ARGB1555,
+    ABGR1555,
+    /// Alpha only
+    A8,
+    /// Luminance, the driver converts colors with BT.709 weights
+    L8,
+    /// Luma plane, then interleaved UV at half width and half height
+    NV12,
+    /// [`Format::NV12`] followed by a full resolution alpha plane
+    ANV12,
+    /// Luma plane, then interleaved UV at half width and full height
+    NV16,
+    /// Luma plane, then U and V planes at half width and half height
+    YV12,
+    /// Luma plane, then U and V planes at full resolution
+    YV24,
+    /// Single plane of `Y0 U Y1 V` pixel pairs
+    YUY2,
+    /// [`Format::YUY2`] followed by a full resolution alpha plane
+    AYUY2,
+    /// Palette indices, several pixels per byte below 8 bits, see [`crate::IndexedImage`]
+    Index1,
+    Index2,
+    Index4,
+    Index8
+}
+
+/// Where each channel lives inside a pixel
+pub(crate) struct Layout {
+    /// `(shift, width)` of r, g, b and a, zero width for absent channels
+    pub(crate) channels: [(u32, u32); 4],
+    /// r, g and b share one gray channel
+    pub(crate) luminance: bool
+}
+
+impl Format {
+    /// Every RGB, alpha and luminance format
+    pub const ALL: [Format; 22] = [
+        Format::RGBA8888, Format::BGRA8888, Format::RGB565, Format::BGR565,
+        Format::RGBX8888, Format::BGRX8888, Format::ARGB8888, Format::ABGR8888,
+        Format::XRGB8888, Format::XBGR8888, Format::RGB888, Format::BGR888,
+        Format::RGBA4444, Format::BGRA4444, Format::ARGB4444, Format::ABGR4444,
+        Format::RGBA5551, Format::BGRA5551, Format::ARGB1555, Format::ABGR1555,
+        Format::A8, Format::L8
+    ];
+
+    /// Source only formats, the GPU converts them to RGB when sampling, see [`crate::YuvStandard`]
+    pub const YUV: [Format; 7] = [
+        Format::NV12, Format::ANV12, Format::NV16, Format::YV12, Format::YV24, Format::YUY2, Format::AYUY2
+    ];
+
+    /// Source only formats looking their colors up in the CLUT
+    pub const INDEXED: [Format; 4] = [Format::Index1, Format::Index2, Format::Index4, Format::Index8];
+
+    /// Bytes per pixel, zero for indexed formats below 8 bits
+    pub(crate) fn bpp(&self) -> u32 {
+        self.bits_per_pixel() / 8
+    }
+
+    /// Bits per pixel of the first plane, the luma plane for multi-planar YUV
+    pub fn bits_per_pixel(&self) -> u32 {
+        match self {
+            Self::Index1 => 1,
+            Self::Index2 => 2,
+            Self::Index4 => 4,
+            Self::A8 | Self::L8 | Self::Index8 |
+            Self::NV12 | Self::ANV12 | Self::NV16 | Self::YV12 | Self::YV24 => 8,
+            Self::YUY2 | Self::AYUY2 => 16,
+            Self::RGB565 | Self::BGR565 |
+            Self::RGBA4444 | Self::BGRA4444 | Self::ARGB4444 | Self::ABGR4444 |
+            Self::RGBA5551 | Self::BGRA5551 | Self::ARGB1555 | Self::ABGR1555 => 16,
+            Self::RGB888 | Self::BGR888 => 24,
+            Self::RGBA8888 | Self::BGRA8888 | Self::RGBX8888 | Self::BGRX8888 |
+            Self::ARGB8888 | Self::ABGR8888 | Self::XRGB8888 | Self::XBGR8888 => 32
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.layout().channels[3].1 > 0 || matches!(self, Self::ANV12 | Self::AYUY2)
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        matches!(self, Self::Index1 | Self::Index2 | Self::Index4 | Self::Index8)
+    }
+
+    /// Number of bits of r, g, b and a, zero for absent channels, YUV and indexed formats
+    pub fn channel_bits(&self) -> [u32; 4] {
+        self.layout().channels.map(|(_, width)| width)
+    }
+
+    pub(crate) fn layout(&self) -> Layout {
+        let channels = match self {
+            Self::RGBA8888 => [(0, 8), (8, 8), (16, 8), (24, 8)],
+            Self::BGRA8888 => [(16, 8), (8, 8), (0, 8), (24, 8)],
+            Self::RGBX8888 => [(0, 8), (8, 8), (16, 8), (0, 0)],
+            Self::BGRX8888 => [(16, 8), (8, 8), (0, 8), (0, 0)],
+            Self::ARGB8888 => [(8, 8), (16, 8), (24, 8), (0, 8)],
+            Self::ABGR8888 => [(24, 8), (16, 8), (8, 8), (0, 8)],
+            Self::XRGB8888 => [(8, 8), (16, 8), (24, 8), (0, 0)],
+            Self::XBGR8888 => [(24, 8), (16, 8), (8, 8), (0, 0)],
+            Self::RGB888 => [(0, 8), (8, 8), (16, 8), (0, 0)],
+            Self::BGR888 => [(16, 8), (8, 8), (0, 8), (0, 0)],
+            Self::RGB565 => [(0, 5), (5, 6), (11, 5), (0, 0)],
+            Self::BGR565 => [(11, 5), (5, 6), (0, 5), (0, 0)],
+            Self::RGBA4444 => [(0, 4), (4, 4), (8, 4), (12, 4)],
+            Self::BGRA4444 => [(8, 4), (4, 4), (0, 4), (12, 4)],
+            Self::ARGB4444 => [(4, 4), (8, 4), (12, 4), (0, 4)],
+            Self::ABGR4444 => [(12, 4), (8, 4), (4, 4), (0, 4)],
+            Self::RGBA5551 => [(0, 5), (5, 5), (10, 5), (15, 1)],
+            Self::BGRA5551 => [(10, 5), (5, 5), (0, 5), (15, 1)],
+            Self::ARGB1555 => [(1, 5), (6, 5), (11, 5), (0, 1)],
+            Self::ABGR1555 => [(11, 5), (6, 5), (1, 5), (0, 1)],
+            Self::A8 => [(0, 0), (0, 0), (0, 0), (0, 8)],
+            Self::L8 => [(0, 8), (0, 8), (0, 8), (0, 0)],
+            Self::NV12 | Self::ANV12 | Self::NV16 | Self::YV12 |
+            Self::YV24 | Self::YUY2 | Self::AYUY2 |
+            Self::Index1 | Self::Index2 | Self::Index4 | Self::Index8 => [(0, 0); 4]
+        };
+        Layout { channels, luminance: matches!(self, Self::L8) }
+    }
+}
+
+impl From<Format> for vg_lite_format_t {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::RGBA8888 => vg_lite_buffer_format_VG_LITE_RGBA8888,
+            Format::BGRA8888 => vg_lite_buffer_format_VG_LITE_BGRA8888,
+            Format::RGB565 => vg_lite_buffer_format_VG_LITE_RGB565,
+            Format::BGR565 => vg_lite_buffer_format_VG_LITE_BGR565,
+            Format::RGBX8888 => vg_lite_buffer_format_VG_LITE_RGBX8888,
+            Format::BGRX8888 => vg_lite_buffer_format_VG_LITE_BGRX8888,
+            Format::ARGB8888 => vg_lite_buffer_format_VG_LITE_ARGB8888,
+            Format::ABGR8888 => vg_lite_buffer_format_VG_LITE_ABGR8888,
+            Format::XRGB8888 => vg_lite_buffer_format_VG_LITE_XRGB8888,
+            Format::XBGR8888 => vg_lite_buffer_format_VG_LITE_XBGR8888,
+            Format::RGB888 => vg_lite_buffer_format_VG_LITE_RGB888,
+            Format::BGR888 => vg_lite_buffer_format_VG_LITE_BGR888,
+            Format::RGBA4444 => vg_lite_buffer_format_VG_LITE_RGBA4444,
+            Format::BGRA4444 => vg_lite_buffer_format_VG_LITE_BGRA4444,
+            Format::ARGB4444 => vg_lite_buffer_format_VG_LITE_ARGB4444,
+            Format::ABGR4444 => vg_lite_buffer_format_VG_LITE_ABGR4444,
+            Format::RGBA5551 => vg_lite_buffer_format_VG_LITE_RGBA5551,
+            Format::BGRA5551 => vg_lite_buffer_format_VG_LITE_BGRA5551,
+            Format::ARGB1555 => vg_lite_buffer_format_VG_LITE_ARGB1555,
+            Format::ABGR1555 => vg_lite_buffer_format_VG_LITE_ABGR1555,
+            Format::A8 => vg_lite_buffer_format_VG_LITE_A8,
+            Format::L8 => vg_lite_buffer_format_VG_LITE_L8,
+            Format::NV12 => vg_lite_buffer_format_VG_LITE_NV12,
+            Format::ANV12 => vg_lite_buffer_format_VG_LITE_ANV12,
+            Format::NV16 => vg_lite_buffer_format_VG_LITE_NV16,
+            Format::YV12 => vg_lite_buffer_format_VG_LITE_YV12,
+            Format::YV24 => vg_lite_buffer_format_VG_LITE_YV24,
+            Format::YUY2 => vg_lite_buffer_format_VG_LITE_YUY2,
+            Format::AYUY2 => vg_lite_buffer_format_VG_LITE_AYUY2,
+            Format::Index1 => vg_lite_buffer_format_VG_LITE_INDEX_1,
+            Format::Index2 => vg_lite_buffer_format_VG_LITE_INDEX_2,
+            Format::Index4 => vg_lite_buffer_format_VG_LITE_INDEX_4,
+            Format::Index8 => vg_lite_buffer_format_VG_LITE_INDEX_8
+        }
+    }
+}
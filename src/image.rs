@@ -0,0 +1,407 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::path::Path as FsPath;
+
+use crate::*;
+
+/// Container formats understood by [`Image::decode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Bmp,
+    /// Netpbm `P2`, `P3`, `P5` and `P6`
+    Ppm
+}
+
+impl ImageFormat {
+    /// Guess the format from the leading magic bytes
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', ..] => Some(Self::Png),
+            [b'B', b'M', ..] => Some(Self::Bmp),
+            [b'P', b'2' | b'3' | b'5' | b'6', ..] => Some(Self::Ppm),
+            _ => None
+        }
+    }
+}
+
+/// How [`Buffer::load_with`] and [`Buffer::from_image_bytes_with`] prepare the decoded pixels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// See [`Image::premultiplied`]
+    pub premultiply: bool
+}
+
+impl LoadOptions {
+    pub(crate) fn apply(self, image: Image) -> Image {
+        match self.premultiply {
+            true => image.premultiplied(),
+            false => image
+        }
+    }
+}
+
+/// How [`Buffer::encode_with`] and [`Buffer::save_png_with`] read the buffer back
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// See [`Image::unpremultiplied`]
+    pub unpremultiply: bool
+}
+
+impl EncodeOptions {
+    pub(crate) fn apply(self, image: Image) -> Image {
+        match self.unpremultiply {
+            true => image.unpremultiplied(),
+            false => image
+        }
+    }
+}
+
+/// Decoded image in CPU memory, straight RGBA
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>
+}
+
+impl Image {
+    /// `pixels` are row major without padding
+    pub fn new(width: u32, height: u32, pixels: Vec<Color>) -> Result<Self, Error> {
+        if pixels.len() != width as usize * height as usize {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(Image { width, height, pixels })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Multiply color channels by alpha, for sources blitted as premultiplied
+    pub fn premultiplied(mut self) -> Self {
+        self.pixels.iter_mut().for_each(|c| *c = c.premultiply());
+        self
+    }
+
+    /// Inverse of [`Image::premultiplied`], for buffers rendered with premultiplied alpha
+    pub fn unpremultiplied(mut self) -> Self {
+        self.pixels.iter_mut().for_each(|c| *c = c.unpremultiply());
+        self
+    }
+
+    pub fn open<P: AsRef<FsPath>>(path: P) -> Result<Self, Error> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    /// Decode PNG, BMP or PPM, detected by [`ImageFormat::detect`]
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        match ImageFormat::detect(bytes) {
+            Some(ImageFormat::Png) => decode_png(bytes),
+            Some(ImageFormat::Bmp) => decode_bmp(bytes),
+            Some(ImageFormat::Ppm) => decode_ppm(bytes),
+            None => Err(Error::NotSupport)
+        }
+    }
+
+    /// Encode as PNG (RGBA), BMP (32 bit with alpha) or binary PPM (alpha is dropped)
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, Error> {
+        match format {
+            ImageFormat::Png => encode_png(self),
+            ImageFormat::Bmp => Ok(encode_bmp(self)),
+            ImageFormat::Ppm => Ok(encode_ppm(self))
+        }
+    }
+
+    pub fn save<P: AsRef<FsPath>>(&self, path: P, format: ImageFormat) -> Result<(), Error> {
+        Ok(std::fs::write(path, self.encode(format)?)?)
+    }
+
+    /// Pack into `format`, rows are `width * bytes_per_pixel` long
+    pub(crate) fn pack(&self, format: Format) -> Vec<u8> {
+        let bpp = format.bpp() as usize;
+        let mut data = Vec::with_capacity(self.pixels.len() * bpp);
+        for color in &self.pixels {
+            data.extend_from_slice(&color.pack(format).to_le_bytes()[..bpp]);
+        }
+        data
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(error: png::DecodingError) -> Self {
+        match error {
+            png::DecodingError::IoError(error) => error.into(),
+            png::DecodingError::LimitsExceeded => Error::OutOfMemory,
+            _ => Error::InvalidImage
+        }
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(error: png::EncodingError) -> Self {
+        match error {
+            png::EncodingError::IoError(error) => error.into(),
+            _ => Error::InvalidArgument
+        }
+    }
+}
+
+fn encode_png(image: &Image) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let data: Vec<u8> = image.pixels.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(bytes)
+}
+
+/// Top down `BI_BITFIELDS` with a V4 header, so that alpha survives
+fn encode_bmp(image: &Image) -> Vec<u8> {
+    const HEADER: u32 = 14 + 108;
+    let size = image.pixels.len() as u32 * 4;
+    let mut bytes = Vec::with_capacity((HEADER + size) as usize);
+    bytes.extend(b"BM");
+    bytes.extend((HEADER + size).to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(HEADER.to_le_bytes());
+    bytes.extend(108u32.to_le_bytes());
+    bytes.extend(image.width.to_le_bytes());
+    bytes.extend((-(image.height as i32)).to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(32u16.to_le_bytes());
+    bytes.extend(3u32.to_le_bytes());
+    bytes.extend(size.to_le_bytes());
+    bytes.extend([0; 16]);
+    for mask in [0x00ff0000u32, 0x0000ff00, 0x000000ff, 0xff000000] {
+        bytes.extend(mask.to_le_bytes());
+    }
+    // LCS_sRGB, endpoints and gamma are unused
+    bytes.extend(b"BGRs");
+    bytes.extend([0; 48]);
+    for c in &image.pixels {
+        bytes.extend([c.b, c.g, c.r, c.a]);
+    }
+    bytes
+}
+
+fn encode_ppm(image: &Image) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    bytes.extend(image.pixels.iter().flat_map(|c| [c.r, c.g, c.b]));
+    bytes
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, Error> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    let channels = info.color_type.samples();
+    let pixels = data[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .flat_map(|row| row[..info.width as usize * channels].chunks_exact(channels))
+        .map(|px| match *px {
+            [l] => Color::rgb(l, l, l),
+            [l, a] => Color::rgba(l, l, l, a),
+            [r, g, b] => Color::rgb(r, g, b),
+            [r, g, b, a] => Color::rgba(r, g, b, a),
+            _ => unreachable!()
+        })
+        .collect();
+    Image::new(info.width, info.height, pixels)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    bytes.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or(Error::InvalidImage)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(Error::InvalidImage)
+}
+
+/// Extract a channel described by a `BI_BITFIELDS` mask and scale it to 8 bits
+fn bitfield(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let value = ((pixel & mask) >> mask.trailing_zeros()) as u64;
+    let max = (mask >> mask.trailing_zeros()) as u64;
+    ((value * 255 + max / 2) / max) as u8
+}
+
+/// Uncompressed BMP: 1, 4 and 8 bit palettes, 16 and 32 bit bitfields, 24 and 32 bit RGB
+fn decode_bmp(bytes: &[u8]) -> Result<Image, Error> {
+    const BI_RGB: u32 = 0;
+    const BI_BITFIELDS: u32 = 3;
+    const BI_ALPHABITFIELDS: u32 = 6;
+
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, 14)? as usize;
+    let width = read_u32(bytes, 18)? as i32;
+    let height = read_u32(bytes, 22)? as i32;
+    let bits = read_u16(bytes, 28)? as u32;
+    let compression = read_u32(bytes, 30)?;
+    if width <= 0 || height == 0 || header_size < 40 {
+        return Err(Error::InvalidImage);
+    }
+    let (width, bottom_up) = (width as u32, height > 0);
+    let height = height.unsigned_abs();
+
+    let masks = match (compression, bits) {
+        (BI_RGB, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (BI_RGB, 32) => [0xff0000, 0xff00, 0xff, 0],
+        (BI_RGB, _) => [0; 4],
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            // masks follow a 40 byte header, or live inside a V4/V5 header
+            let alpha = if compression == BI_ALPHABITFIELDS || header_size >= 56 {
+                read_u32(bytes, 54 + 12)?
+            } else {
+                0
+            };
+            [read_u32(bytes, 54)?, read_u32(bytes, 58)?, read_u32(bytes, 62)?, alpha]
+        }
+        _ => return Err(Error::NotSupport)
+    };
+
+    let palette: Vec<Color> = if bits <= 8 {
+        let colors = match read_u32(bytes, 46)? {
+            0 => 1 << bits,
+            n => n as usize
+        };
+        let start = 14 + header_size;
+        let end = colors.checked_mul(4).and_then(|size| size.checked_add(start)).ok_or(Error::InvalidImage)?;
+        let table = bytes.get(start..end).ok_or(Error::InvalidImage)?;
+        table.chunks_exact(4).map(|c| Color::rgb(c[2], c[1], c[0])).collect()
+    } else {
+        Vec::new()
+    };
+
+    let stride = (width as usize).checked_mul(bits as usize).ok_or(Error::InvalidImage)?.div_ceil(32) * 4;
+    // the header dimensions are only trusted once the file holds every row
+    let end = stride.checked_mul(height as usize).and_then(|size| size.checked_add(data_offset));
+    if end.is_none_or(|end| end > bytes.len()) {
+        return Err(Error::InvalidImage);
+    }
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height as usize {
+        let row = if bottom_up { height as usize - 1 - y } else { y };
+        let start = data_offset + row * stride;
+        let row = bytes.get(start..start + stride).ok_or(Error::InvalidImage)?;
+        for x in 0..width as usize {
+            let color = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let index = (row[bit / 8] as u32 >> (8 - bits as usize - bit % 8)) & ((1 << bits) - 1);
+                    *palette.get(index as usize).ok_or(Error::InvalidImage)?
+                }
+                24 => Color::rgb(row[x * 3 + 2], row[x * 3 + 1], row[x * 3]),
+                16 | 32 => {
+                    let pixel = if bits == 16 {
+                        read_u16(row, x * 2)? as u32
+                    } else {
+                        read_u32(row, x * 4)?
+                    };
+                    let [r, g, b, a] = masks.map(|mask| bitfield(pixel, mask));
+                    Color::rgba(r, g, b, if masks[3] == 0 { 255 } else { a })
+                }
+                _ => return Err(Error::NotSupport)
+            };
+            pixels.push(color);
+        }
+    }
+    Image::new(width, height, pixels)
+}
+
+/// Netpbm header fields and samples, skipping `#` comments
+struct PnmTokens<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl PnmTokens<'_> {
+    fn next(&mut self) -> Result<u32, Error> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(b'#') => {
+                    while !matches!(self.bytes.get(self.position), Some(b'\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.position += 1,
+                _ => break
+            }
+        }
+        let start = self.position;
+        while self.bytes.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(Error::InvalidImage)
+    }
+}
+
+fn decode_ppm(bytes: &[u8]) -> Result<Image, Error> {
+    let (ascii, channels) = match &bytes[..2] {
+        b"P2" => (true, 1),
+        b"P3" => (true, 3),
+        b"P5" => (false, 1),
+        b"P6" => (false, 3),
+        _ => return Err(Error::NotSupport)
+    };
+    let mut tokens = PnmTokens { bytes, position: 2 };
+    let width = tokens.next()?;
+    let height = tokens.next()?;
+    let max = tokens.next()?;
+    if max == 0 || max > 65535 {
+        return Err(Error::InvalidImage);
+    }
+    let count = (width as usize).checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or(Error::InvalidImage)?;
+    // every sample takes at least a byte
+    if count > bytes.len() {
+        return Err(Error::InvalidImage);
+    }
+    let samples: Vec<u32> = if ascii {
+        (0..count).map(|_| tokens.next()).collect::<Result<_, _>>()?
+    } else {
+        // a single whitespace separates the header from the raster
+        let start = tokens.position + 1;
+        let size = if max > 255 { 2 } else { 1 };
+        let end = count.checked_mul(size).and_then(|size| size.checked_add(start)).ok_or(Error::InvalidImage)?;
+        let raster = bytes.get(start..end).ok_or(Error::InvalidImage)?;
+        raster.chunks_exact(size)
+            .map(|s| s.iter().fold(0, |v, &b| (v << 8) | b as u32))
+            .collect()
+    };
+    let scale = |s: u32| ((s.min(max) as u64 * 255 + max as u64 / 2) / max as u64) as u8;
+    let pixels = samples.chunks_exact(channels)
+        .map(|px| match *px {
+            [l] => Color::rgb(scale(l), scale(l), scale(l)),
+            [r, g, b] => Color::rgb(scale(r), scale(g), scale(b)),
+            _ => unreachable!()
+        })
+        .collect();
+    Image::new(width, height, pixels)
+}
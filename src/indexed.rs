@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::collections::HashMap;
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Order of the pixels packed in one byte of indexed formats below 8 bits
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IndexEndian {
+    /// First pixel in the least significant bits
+    #[default]
+    Little,
+    /// First pixel in the most significant bits
+    Big
+}
+
+impl From<IndexEndian> for vg_lite_index_endian {
+    fn from(endian: IndexEndian) -> Self {
+        match endian {
+            IndexEndian::Little => vg_lite_index_endian_VG_LITE_INDEX_LITTLE_ENDIAN,
+            IndexEndian::Big => vg_lite_index_endian_VG_LITE_INDEX_BIG_ENDIAN
+        }
+    }
+}
+
+/// Palette entries as expected by `vg_lite_set_CLUT`: `0xAARRGGBB`, padded with transparent
+/// entries to the `2^bits` colors of `format`
+pub(crate) fn clut(palette: &[Color], format: Format) -> Vec<u32> {
+    let mut colors: Vec<u32> = palette.iter()
+        .map(|c| (c.a as u32) << 24 | (c.r as u32) << 16 | (c.g as u32) << 8 | c.b as u32)
+        .collect();
+    colors.resize(1 << format.bits_per_pixel(), 0);
+    colors
+}
+
+/// Index of pixel `x` in a row of packed indices
+pub(crate) fn read_index(row: &[u8], x: u32, format: Format, endian: IndexEndian) -> u8 {
+    let bits = format.bits_per_pixel();
+    let per_byte = 8 / bits;
+    let slot = match endian {
+        IndexEndian::Little => x % per_byte,
+        IndexEndian::Big => per_byte - 1 - x % per_byte
+    };
+    let mask = ((1u32 << bits) - 1) as u8;
+    (row[(x / per_byte) as usize] >> (slot * bits)) & mask
+}
+
+/// Image made of palette indices, one byte per pixel in CPU memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedImage {
+    width: u32,
+    height: u32,
+    format: Format,
+    palette: Vec<Color>,
+    indices: Vec<u8>
+}
+
+impl IndexedImage {
+    /// `format` is one of [`Format::INDEXED`], the palette may be shorter than the format allows
+    pub fn new(width: u32, height: u32, format: Format, palette: Vec<Color>, indices: Vec<u8>) -> Result<Self, Error> {
+        if !format.is_indexed()
+            || palette.len() > 1 << format.bits_per_pixel()
+            || indices.len() != width as usize * height as usize
+            || indices.iter().any(|&i| i as usize >= palette.len()) {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(IndexedImage { width, height, format, palette, indices })
+    }
+
+    /// Unpack indices stored with rows padded to whole bytes, as kept in ROM
+    pub fn from_packed(
+        width: u32,
+        height: u32,
+        format: Format,
+        endian: IndexEndian,
+        palette: Vec<Color>,
+        data: &[u8]
+    ) -> Result<Self, Error> {
+        if !format.is_indexed() {
+            return Err(Error::InvalidArgument);
+        }
+        let row = (width * format.bits_per_pixel()).div_ceil(8) as usize;
+        if data.len() < row * height as usize {
+            return Err(Error::InvalidArgument);
+        }
+        let indices = (0..height)
+            .flat_map(|y| {
+                let row = &data[y as usize * row..][..row];
+                (0..width).map(move |x| read_index(row, x, format, endian))
+            })
+            .collect();
+        Self::new(width, height, format, palette, indices)
+    }
+
+    /// Reduce an image to at most `colors` colors with median cut, `colors` is 2, 4, 16 or 256.
+    /// Images that already have few enough colors are converted without loss
+    pub fn quantize(image: &Image, colors: usize) -> Result<Self, Error> {
+        let format = match colors {
+            2 => Format::Index1,
+            4 => Format::Index2,
+            16 => Format::Index4,
+            256 => Format::Index8,
+            _ => return Err(Error::InvalidArgument)
+        };
+        let mut histogram: HashMap<Color, u32> = HashMap::new();
+        image.pixels().iter().for_each(|c| *histogram.entry(*c).or_default() += 1);
+        let mut entries: Vec<(Color, u32)> = histogram.into_iter().collect();
+        // deterministic palettes whatever the hash order
+        entries.sort_unstable_by_key(|(c, _)| u32::from_le_bytes([c.r, c.g, c.b, c.a]));
+
+        let palette = if entries.len() <= colors {
+            entries.iter().map(|(c, _)| *c).collect()
+        } else {
+            median_cut(entries, colors)
+        };
+        let mut nearest: HashMap<Color, u8> = HashMap::new();
+        let indices = image.pixels().iter()
+            .map(|c| *nearest.entry(*c).or_insert_with(|| closest(&palette, *c)))
+            .collect();
+        Self::new(image.width(), image.height(), format, palette, indices)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// Row major, one byte per pixel
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    pub fn index(&self, x: u32, y: u32) -> u8 {
+        self.indices[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.palette[self.index(x, y) as usize]
+    }
+
+    /// Indices packed in the format's bits, rows padded to whole bytes
+    pub fn pack(&self, endian: IndexEndian) -> Vec<u8> {
+        let bits = self.format.bits_per_pixel();
+        let per_byte = 8 / bits;
+        let row = (self.width * bits).div_ceil(8) as usize;
+        let mut data = vec![0; row * self.height as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let slot = match endian {
+                    IndexEndian::Little => x % per_byte,
+                    IndexEndian::Big => per_byte - 1 - x % per_byte
+                };
+                data[y as usize * row + (x / per_byte) as usize] |= self.index(x, y) << (slot * bits);
+            }
+        }
+        data
+    }
+
+    pub fn to_image(&self) -> Image {
+        let pixels = self.indices.iter().map(|&i| self.palette[i as usize]).collect();
+        Image::new(self.width, self.height, pixels).unwrap()
+    }
+}
+
+fn channels(c: Color) -> [u8; 4] {
+    [c.r, c.g, c.b, c.a]
+}
+
+fn closest(palette: &[Color], color: Color) -> u8 {
+    let distance = |p: &Color| channels(*p).iter()
+        .zip(channels(color))
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2))
+        .sum::<i32>();
+    (0..palette.len()).min_by_key(|&i| distance(&palette[i])).unwrap() as u8
+}
+
+/// Split the color space into `colors` boxes, each time cutting the box with the widest
+/// channel range at the median pixel along that channel, then average each box
+fn median_cut(entries: Vec<(Color, u32)>, colors: usize) -> Vec<Color> {
+    let widest = |entries: &[(Color, u32)]| (0..4)
+        .map(|channel| {
+            let values = entries.iter().map(|(c, _)| channels(*c)[channel]);
+            let range = values.clone().max().unwrap() - values.min().unwrap();
+            (range, channel)
+        })
+        .max()
+        .unwrap();
+    let mut boxes = vec![entries];
+    while boxes.len() < colors {
+        let Some((index, (_, channel))) = boxes.iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (i, widest(b)))
+            .max_by_key(|(_, (range, _))| *range) else { break };
+        let mut split = boxes.swap_remove(index);
+        split.sort_unstable_by_key(|(c, _)| channels(*c)[channel]);
+        let total: u32 = split.iter().map(|(_, n)| n).sum();
+        let mut seen = 0;
+        let median = split.iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .unwrap();
+        // both halves keep at least one color
+        let rest = split.split_off((median + 1).min(split.len() - 1));
+        boxes.push(split);
+        boxes.push(rest);
+    }
+    boxes.iter().map(|b| {
+        let total: u64 = b.iter().map(|(_, n)| *n as u64).sum();
+        let mean = |channel: usize| {
+            let sum: u64 = b.iter().map(|(c, n)| channels(*c)[channel] as u64 * *n as u64).sum();
+            ((sum + total / 2) / total) as u8
+        };
+        Color::rgba(mean(0), mean(1), mean(2), mean(3))
+    }).collect()
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Submission the next draws go into, closed by every flush and finish
+static OPEN: AtomicU64 = AtomicU64::new(1);
+/// Last submission the GPU is known to be done with
+static COMPLETED: AtomicU64 = AtomicU64::new(0);
+
+/// What the driver still has to release of a dropped resource
+pub(crate) enum Release {
+    /// Allocated buffer, the planes allocated for it and its accounted bytes
+    Free(vg_lite_buffer, Vec<vg_lite_buffer>, u64),
+    /// Mapped buffer and the planes allocated for it
+    Unmap(vg_lite_buffer, Vec<vg_lite_buffer>),
+    /// Path and the data it points to
+    ClearPath(vg_lite_path, Box<dyn Any>)
+}
+
+thread_local! {
+    /// Releases waiting for their submission, resources are released on the thread dropping them
+    static DEFERRED: RefCell<Vec<(u64, Release)>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Release {
+    fn run(self) {
+        match self {
+            Release::Free(mut buffer, planes, allocated) => {
+                free_planes(planes);
+                if allocated > 0 {
+                    memory::untrack(allocated);
+                }
+                driver(|| unsafe { vg_lite_free(&mut buffer); });
+            }
+            Release::Unmap(mut buffer, planes) => {
+                free_planes(planes);
+                driver(|| unsafe { vg_lite_unmap(&mut buffer); });
+            }
+            Release::ClearPath(mut path, data) => {
+                driver(|| unsafe { vg_lite_clear_path(&mut path); });
+                drop(data);
+            }
+        }
+    }
+}
+
+fn free_planes(planes: Vec<vg_lite_buffer>) {
+    for mut plane in planes {
+        driver(|| unsafe { vg_lite_free(&mut plane); });
+    }
+}
+
+/// Submission a draw issued now belongs to
+pub(crate) fn submission() -> u64 {
+    OPEN.load(Ordering::Acquire)
+}
+
+/// Close the open submission, returns its id
+pub(crate) fn close() -> u64 {
+    OPEN.fetch_add(1, Ordering::AcqRel)
+}
+
+/// The GPU is done with `submission` and every one before it
+pub(crate) fn complete(submission: u64) {
+    COMPLETED.fetch_max(submission, Ordering::AcqRel);
+}
+
+/// Whether the GPU may still read a resource last used in `submission`
+pub(crate) fn in_flight(submission: u64) -> bool {
+    submission > COMPLETED.load(Ordering::Acquire)
+}
+
+/// Release now if the GPU is done with `last_use`, otherwise once its submission completes
+pub(crate) fn release(last_use: u64, release: Release) {
+    collect();
+    match in_flight(last_use) {
+        true => DEFERRED.with(|deferred| deferred.borrow_mut().push((last_use, release))),
+        false => release.run()
+    }
+}
+
+/// Run the releases whose submission completed
+pub(crate) fn collect() {
+    let done: Vec<_> = DEFERRED.with(|deferred| {
+        let mut deferred = deferred.borrow_mut();
+        let (done, waiting) = deferred.drain(..).partition(|(submission, _)| !in_flight(*submission));
+        *deferred = waiting;
+        done
+    });
+    done.into_iter().for_each(|(_, release)| release.run());
+}
+
+/// Wait for everything submitted so far, then run the releases it held back
+pub(crate) fn finish() -> Result<(), Error> {
+    let submission = close();
+    wrap_result(driver(|| unsafe { vg_lite_finish() }), ())?;
+    complete(submission);
+    collect();
+    Ok(())
+}
+
+/// Releases waiting for their submission on this thread
+pub(crate) fn deferred() -> usize {
+    DEFERRED.with(|deferred| deferred.borrow().len())
+}
+
+impl Context {
+    /// Dropped buffers and paths the GPU may still read, released by the [`Context::finish`],
+    /// [`Context::flush`] or allocation after it is done with them
+    pub fn in_flight(&self) -> usize {
+        deferred()
+    }
+}
@@ -3,96 +3,118 @@
 mod vg_lite;
 mod path;
 mod transform;
+mod color;
+mod format;
+mod image;
+mod convert;
+mod yuv;
+mod indexed;
+mod atlas;
+mod alpha;
+mod color_key;
+mod color_matrix;
+mod blur;
+mod color_transform;
+mod settings;
+mod state;
+mod tess;
+mod memory;
+mod pool;
+mod fence;
+mod inflight;
+#[cfg(miri)]
+mod miri;
 
 use vg_lite::*;
 pub use path::*;
 pub use transform::*;
-use std::{ffi::c_void, ptr::null_mut};
+pub use color::*;
+pub use format::*;
+pub use image::*;
+pub use convert::*;
+pub use yuv::*;
+pub use indexed::*;
+pub use atlas::*;
+pub use alpha::*;
+pub use color_key::*;
+pub use color_matrix::*;
+pub use blur::*;
+pub use color_transform::*;
+pub use settings::*;
+pub use state::*;
+pub use memory::*;
+pub use pool::*;
+pub use fence::*;
+use std::{cell::{Cell, RefCell}, ffi::c_void, marker::PhantomData, ptr::null_mut, rc::Rc, sync::Mutex};
+
+pub struct Context {
+    /// Global settings as last set through this context, see [`Context::with_state`]
+    state: RefCell<RenderState<'static>>,
+    /// Mask layer in use, the `mask` of `state` is always `None`
+    mask: Cell<*mut vg_lite_buffer>,
+    /// Started by the first [`Context::flush`]
+    fences: RefCell<Option<FenceWorker>>
+}
 
-pub struct Context(());
 impl Context {
     /// Can be called before [`Context::new`] to overwrite the default value: 65536
     pub fn set_command_size(size: u32) -> Result<(), Error> {
-        wrap_result(unsafe {
+        wrap_result(driver(|| unsafe {
             vg_lite_set_command_buffer_size(size)
-        }, ())
+        }), ())
     }
     pub fn new(tess_width: u32, tess_height: u32) -> Result<Self, Error> {
-        wrap_result(unsafe {
+        let context = wrap_result(driver(|| unsafe {
             vg_lite_init(tess_width as i32, tess_height as i32)
-        }, Context(()))
+        }), Context { state: RefCell::default(), mask: Cell::new(null_mut()), fences: RefCell::default() })?;
+        tess::set_window(tess_width, tess_height);
+        Ok(context)
     }
-    /// Do drawing with blocking
+    /// Do drawing with blocking, then release the buffers and paths dropped meanwhile
     pub fn finish(&self) -> Result<(), Error> {
-        wrap_result(unsafe { vg_lite_finish() }, ())
+        inflight::finish()
     }
-    /// Do drawing without blocking
-    pub fn flush(&self) -> Result<(), Error> {
-        wrap_result(unsafe { vg_lite_flush() }, ())
+    /// Do drawing without blocking, the fence tells when the GPU is done
+    pub fn flush(&self) -> Result<Fence, Error> {
+        inflight::collect();
+        wrap_result(driver(|| unsafe { vg_lite_flush() }), ())?;
+        Ok(self.fence(inflight::close()))
     }
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
-        unsafe { vg_lite_close(); }
+        self.stop_fences();
+        let _ = inflight::finish();
+        driver(|| unsafe { vg_lite_close(); });
+        tess::set_window(0, 0);
     }
 }
 
 enum BufferSource {
-    #[allow(dead_code)]
+    /// Not backed yet, or a view into another buffer's memory
     None,
     Allocated,
-    Mapped
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum Format {
-    RGBA8888,
-    BGRA8888,
-    RGB565,
-    BGR565
+    Mapped,
+    /// Mapped memory borrowed from the caller by [`Buffer::map_user_memory`]
+    UserMemory
 }
 
-impl Format {
-    fn bpp(&self) -> u32 {
-        match self {
-            Self::BGR565 | Self::RGB565 => 2,
-            Self::BGRA8888 | Self::RGBA8888 => 4
-        }
-    }
-}
-
-impl From<Format> for vg_lite_format_t {
-    fn from(format: Format) -> Self {
-        match format {
-            Format::RGBA8888 => vg_lite_buffer_format_VG_LITE_RGBA8888,
-            Format::BGRA8888 => vg_lite_buffer_format_VG_LITE_BGRA8888,
-            Format::RGB565 => vg_lite_buffer_format_VG_LITE_RGB565,
-            Format::BGR565 => vg_lite_buffer_format_VG_LITE_BGR565
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
-pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b:u8,
-    pub a: u8
-}
-
-impl Into<u32> for Color {
-    fn into(self) -> u32 {
-        ((self.a as u32) << 24) |
-        ((self.r as u32) << 16) |
-        ((self.g as u32) << 8) |
-        (self.b as u32)
-    }
-}
-
-pub struct Buffer {
+/// GPU accessible pixels, `'a` is the lifetime of the memory borrowed by [`Buffer::map_user_memory`],
+/// buffers owning their memory can pick any lifetime
+pub struct Buffer<'a> {
     buffer: vg_lite_buffer,
     source: BufferSource,
+    format: Format,
+    /// Chroma and alpha planes of YUV formats the driver didn't set up itself
+    planes: Vec<(Plane, vg_lite_buffer)>,
+    /// Colors of indexed formats, loaded into the CLUT whenever the buffer is a source
+    palette: Vec<Color>,
+    /// Bytes accounted for by [`Buffer::allocate`], see [`Context::memory_stats`]
+    allocated: u64,
+    /// Last submission reading or writing the memory, shared with the views of the buffer
+    last_use: Rc<Cell<u64>>,
+    borrow: PhantomData<&'a mut [u8]>
 }
 
 impl Default for vg_lite_buffer {
@@ -149,7 +171,17 @@ pub enum Error {
     NotAligned,
     FlexaTimeOut,
     FlexaHandshakeFail,
-    Unknown
+    Unknown,
+    /// Reading or writing a file failed
+    Io(std::io::ErrorKind),
+    /// Malformed image data
+    InvalidImage,
+    /// A draw covers more than the tessellation window, see [`Context::set_tess_window_check`]
+    TessWindowExceeded,
+    /// A path coordinate is out of the range of its new format, see [`PathData::convert`]
+    Overflow,
+    /// A path coordinate would be rounded by its new format, see [`PathData::convert`]
+    PrecisionLoss
 }
 
 impl From<vg_lite_error> for Error {
@@ -171,6 +203,27 @@ impl From<vg_lite_error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error.kind())
+    }
+}
+
+/// Held around every driver call: the driver isn't thread-safe, and the fence thread reads
+/// the GPU state while the drawing thread keeps recording
+static DRIVER: Mutex<()> = Mutex::new(());
+
+/// Run `call` alone in the driver, it must not call back into this crate
+fn driver<R>(call: impl FnOnce() -> R) -> R {
+    let _driver = DRIVER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    call()
+}
+
+/// Whether the GPU has `feature`, one of the `gcFEATURE_BIT_VG_*` bits
+fn has_feature(feature: vg_lite_feature) -> bool {
+    driver(|| unsafe { vg_lite_query_feature(feature) != 0 })
+}
+
 fn wrap_result<T>(error: vg_lite_error, t: T) -> Result<T, Error> {
     if error == vg_lite_error_VG_LITE_SUCCESS {
         Ok(t)
@@ -182,43 +235,549 @@ fn wrap_result<T>(error: vg_lite_error, t: T) -> Result<T, Error> {
 
 pub type Rectangle = vg_lite_rectangle;
 
-impl Buffer {
-    pub fn allocate(width: u32, height: u32, format: Format) -> Result<Self, Error> {
-        let mut buffer = Buffer {
+/// Buffer sharing the memory of a region of another one, see [`Buffer::region`]
+pub type BufferView<'a> = Buffer<'a>;
+
+/// Byte alignment of the first pixel of a buffer required by the GPU
+pub const ADDRESS_ALIGNMENT: u32 = 64;
+
+/// `offset` bytes into `memory`, null stays null for buffers without a CPU mapping
+fn offset_memory(memory: *mut c_void, offset: u32) -> *mut c_void {
+    match memory.is_null() {
+        true => memory,
+        false => (memory as *mut u8).wrapping_add(offset as usize) as *mut c_void
+    }
+}
+
+impl<'a> Buffer<'a> {
+    /// Description only, no memory behind it yet
+    fn unbacked(width: u32, height: u32, format: Format) -> Self {
+        Buffer {
             buffer: vg_lite_buffer::new(width as i32, height as i32, format.into()),
-            source: BufferSource::Allocated
-        };
-        let error = unsafe {
+            source: BufferSource::None,
+            format,
+            planes: Vec::new(),
+            palette: Vec::new(),
+            allocated: 0,
+            last_use: Rc::default(),
+            borrow: PhantomData
+        }
+    }
+
+    /// GPU memory for a `width * height` buffer, counted in [`Context::memory_stats`].
+    /// Registered caches are evicted to stay within the memory budget or when the driver runs out,
+    /// waiting for the GPU if it still reads what they freed
+    pub fn allocate(width: u32, height: u32, format: Format) -> Result<Self, Error> {
+        inflight::collect();
+        let estimate = memory::estimate(width, height, format);
+        memory::reserve(estimate)?;
+        let mut buffer = match Self::allocate_untracked(width, height, format) {
+            Err(Error::OutOfMemory) if memory::evict(estimate) > 0 || inflight::deferred() > 0 => {
+                // evicted buffers the GPU may still read are only freed once it is done with them
+                inflight::finish()?;
+                Self::allocate_untracked(width, height, format)
+            }
+            result => result
+        }?;
+        buffer.allocated = buffer.allocated_bytes();
+        memory::track(buffer.allocated);
+        Ok(buffer)
+    }
+
+    /// Bytes of GPU memory owned by this buffer, zero for views and mapped memory
+    pub fn allocated_bytes(&self) -> u64 {
+        if !matches!(self.source, BufferSource::Allocated) {
+            return 0;
+        }
+        memory::estimate(self.width(), self.height(), self.format)
+    }
+
+    fn allocate_untracked(width: u32, height: u32, format: Format) -> Result<Self, Error> {
+        let mut buffer = Self::unbacked(width, height, format);
+        let error = driver(|| unsafe {
             vg_lite_allocate(&mut buffer.buffer)
+        });
+        let mut buffer = wrap_result(error, buffer)?;
+        buffer.source = BufferSource::Allocated;
+        for layout in format.planes(width, height).into_iter().skip(1) {
+            let yuv = &buffer.buffer.yuv;
+            let present = match layout.plane {
+                Plane::Chroma => !yuv.uv_memory.is_null(),
+                Plane::V => !yuv.v_memory.is_null(),
+                _ => yuv.alpha_planar != 0
+            };
+            if present {
+                continue;
+            }
+            let mut plane = vg_lite_buffer::new(
+                layout.row_bytes as i32, layout.rows as i32, vg_lite_buffer_format_VG_LITE_A8
+            );
+            wrap_result(driver(|| unsafe { vg_lite_allocate(&mut plane) }), ())?;
+            buffer.attach_plane(layout.plane, plane);
+        }
+        Ok(buffer)
+    }
+
+    /// Bytes needed to map a buffer whose first plane has rows `stride` bytes apart,
+    /// the other planes of YUV formats follow it without padding, see [`Format::planes`]
+    pub fn mapped_size(width: u32, height: u32, stride: u32, format: Format) -> Result<usize, Error> {
+        let layouts = format.planes(width, height);
+        if stride < layouts[0].row_bytes {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(stride as usize * height as usize + layouts[1..].iter()
+            .map(|l| l.row_bytes as usize * l.rows as usize)
+            .sum::<usize>())
+    }
+
+    /// Import the dma-buf `fd`, the pixels start `offset` bytes into it. `memory` is the CPU mapping
+    /// of the whole dma-buf, it may be null if the CPU never touches the pixels: [`Buffer::data`],
+    /// [`Buffer::pixel`] and [`Buffer::upload`] then fail with [`Error::NotSupport`].
+    /// `stride` must be a multiple of [`ADDRESS_ALIGNMENT`]
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be a dma-buf. Unless null, `memory` must be valid for reads and writes of
+    /// `offset` plus [`Buffer::mapped_size`] bytes for as long as the buffer lives
+    pub unsafe fn map_dmabuf(
+        fd: i32,
+        offset: u32,
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: Format,
+        memory: *mut c_void
+    ) -> Result<Self, Error> {
+        Self::mapped_size(width, height, stride, format)?;
+        let memory = offset_memory(memory, offset);
+        let mut buffer = Self::map(width, height, stride, format, memory, vg_lite_map_flag_VG_LITE_MAP_DMABUF, fd)?;
+        // the driver returns the address of the start of the dma-buf
+        buffer.buffer.address += offset;
+        buffer.map_planes(memory);
+        Ok(buffer)
+    }
+
+    /// Let the GPU render to or read from CPU memory in place, the buffer borrows `data` for its whole life.
+    /// Call [`Buffer::flush_mapped`] after writing to `data` from the CPU. Dropping the buffer while
+    /// the GPU may still use it waits for the GPU. `stride` must be a multiple of [`ADDRESS_ALIGNMENT`]
+    pub fn map_user_memory(data: &'a mut [u8], width: u32, height: u32, stride: u32, format: Format) -> Result<Self, Error> {
+        if data.len() < Self::mapped_size(width, height, stride, format)? {
+            return Err(Error::InvalidArgument);
+        }
+        let memory = data.as_mut_ptr() as *mut c_void;
+        let mut buffer = Self::map(width, height, stride, format, memory, vg_lite_map_flag_VG_LITE_MAP_USER_MEMORY, -1)?;
+        buffer.source = BufferSource::UserMemory;
+        buffer.map_planes(memory);
+        Ok(buffer)
+    }
+
+    fn map(
+        width: u32,
+        height: u32,
+        stride: u32,
+        format: Format,
+        memory: *mut c_void,
+        flag: vg_lite_map_flag,
+        fd: i32
+    ) -> Result<Self, Error> {
+        // every row has to start on an aligned address like the first one
+        if !stride.is_multiple_of(ADDRESS_ALIGNMENT) {
+            return Err(Error::InvalidArgument);
+        }
+        let mut buffer = Self::unbacked(width, height, format);
+        buffer.buffer.stride = stride as i32;
+        buffer.buffer.memory = memory;
+        wrap_result(driver(|| unsafe {
+            vg_lite_map(&mut buffer.buffer, flag, fd)
+        }), ())?;
+        buffer.source = BufferSource::Mapped;
+        Ok(buffer)
+    }
+
+    /// Other planes of YUV formats follow the first one, once the address of the latter is known
+    fn map_planes(&mut self, memory: *mut c_void) {
+        let layouts = self.format.planes(self.width(), self.height());
+        let mut offset = self.stride() * self.height();
+        for layout in layouts.into_iter().skip(1) {
+            let mut plane = vg_lite_buffer::new(
+                layout.row_bytes as i32, layout.rows as i32, vg_lite_buffer_format_VG_LITE_A8
+            );
+            plane.stride = layout.row_bytes as i32;
+            plane.address = self.buffer.address + offset;
+            plane.memory = offset_memory(memory, offset);
+            self.attach_plane(layout.plane, plane);
+            offset += layout.row_bytes * layout.rows;
+        }
+    }
+
+    /// Pixels of `rect`, sharing this buffer's memory and stride, as a source or a target.
+    /// The first pixel of the region must lie on [`ADDRESS_ALIGNMENT`] bytes
+    pub fn region(&mut self, rect: Rectangle) -> Result<BufferView<'_>, Error> {
+        if self.format.is_yuv() || self.format.bits_per_pixel() < 8 {
+            return Err(Error::NotSupport);
+        }
+        if rect.x < 0 || rect.y < 0 || rect.width <= 0 || rect.height <= 0
+            || rect.x + rect.width > self.buffer.width || rect.y + rect.height > self.buffer.height {
+            return Err(Error::InvalidArgument);
+        }
+        let offset = rect.y as u32 * self.stride() + rect.x as u32 * self.format.bpp();
+        if !(self.buffer.address + offset).is_multiple_of(ADDRESS_ALIGNMENT) {
+            return Err(Error::NotAligned);
+        }
+        let mut view = Buffer::unbacked(rect.width as u32, rect.height as u32, self.format);
+        view.buffer = vg_lite_buffer {
+            width: rect.width,
+            height: rect.height,
+            memory: offset_memory(self.buffer.memory, offset),
+            address: self.buffer.address + offset,
+            ..self.buffer
         };
-        dbg!(buffer.buffer.memory);
-        wrap_result(error, buffer)
+        view.palette = self.palette.clone();
+        view.last_use = self.last_use.clone();
+        Ok(view)
     }
 
-    pub fn map(width: u32, height: u32, format: Format, dmabuf_fd: i32, memory: *mut c_void) -> Result<Self, Error> {
-        let mut buffer = Buffer {
-            buffer: vg_lite_buffer::new(width as i32, height as i32, format.into()),
-            source: BufferSource::Mapped
+    /// Write back CPU caches of a mapped buffer, needed after the CPU wrote to its memory
+    /// and before the GPU reads it
+    pub fn flush_mapped(&mut self) -> Result<(), Error> {
+        if !matches!(self.source, BufferSource::Mapped | BufferSource::UserMemory) {
+            return Err(Error::InvalidArgument);
+        }
+        wrap_result(driver(|| unsafe { vg_lite_flush_mapped_buffer(&mut self.buffer) }), ())
+    }
+
+    /// Point the YUV info of the buffer at `plane`, its handle is freed with the buffer unless null
+    fn attach_plane(&mut self, kind: Plane, plane: vg_lite_buffer) {
+        let yuv = &mut self.buffer.yuv;
+        match kind {
+            Plane::Chroma => {
+                yuv.uv_planar = plane.address;
+                yuv.uv_memory = plane.memory;
+                yuv.uv_handle = plane.handle;
+                yuv.uv_stride = plane.stride as u32;
+                yuv.uv_height = plane.height as u32;
+            }
+            Plane::V => {
+                yuv.v_planar = plane.address;
+                yuv.v_memory = plane.memory;
+                yuv.v_handle = plane.handle;
+                yuv.v_stride = plane.stride as u32;
+                yuv.v_height = plane.height as u32;
+            }
+            Plane::Alpha => {
+                yuv.alpha_planar = plane.address;
+                yuv.alpha_stride = plane.stride as u32;
+            }
+            Plane::Primary => unreachable!()
+        }
+        self.planes.push((kind, plane));
+    }
+
+    /// Memory and stride of one plane, `None` if the buffer doesn't have it or it isn't CPU visible
+    fn plane_data(&self, kind: Plane) -> Option<(&[u8], usize)> {
+        let yuv = &self.buffer.yuv;
+        let (memory, stride, rows) = match kind {
+            Plane::Primary => return Some((self.data().ok()?, self.stride() as usize)),
+            Plane::Chroma => (yuv.uv_memory, yuv.uv_stride, yuv.uv_height),
+            Plane::V => (yuv.v_memory, yuv.v_stride, yuv.v_height),
+            Plane::Alpha => self.planes.iter()
+                .find(|(plane, _)| *plane == Plane::Alpha)
+                .map(|(_, b)| (b.memory, b.stride as u32, b.height as u32))?
         };
-        buffer.buffer.address = 0xdeaddead;
-        buffer.buffer.stride = (width * format.bpp()) as i32;
-        buffer.buffer.memory = memory;
-        wrap_result(unsafe {
-            vg_lite_map(&mut buffer.buffer, vg_lite_map_flag_VG_LITE_MAP_DMABUF, dmabuf_fd)
-        }, buffer)
+        if memory.is_null() {
+            return None;
+        }
+        let data = unsafe { core::slice::from_raw_parts(memory as *const u8, (stride * rows) as usize) };
+        Some((data, stride as usize))
+    }
+
+    /// Allocate a buffer of `format` and upload a decoded image into it, YUV and indexed formats
+    /// aren't supported, see [`Buffer::from_indexed`]
+    pub fn from_image(image: &Image, format: Format) -> Result<Self, Error> {
+        if format.is_yuv() || format.is_indexed() {
+            return Err(Error::NotSupport);
+        }
+        let mut buffer = Self::allocate(image.width(), image.height(), format)?;
+        buffer.upload(&image.pack(format), image.width() * format.bpp())?;
+        Ok(buffer)
+    }
+
+    /// Allocate a buffer of the image's indexed format, upload its indices and keep its palette
+    pub fn from_indexed(image: &IndexedImage, endian: IndexEndian) -> Result<Self, Error> {
+        let mut buffer = Self::allocate(image.width(), image.height(), image.format())?;
+        buffer.set_index_endian(endian);
+        buffer.upload(&image.pack(endian), (image.width() * image.format().bits_per_pixel()).div_ceil(8))?;
+        buffer.set_palette(image.palette())?;
+        Ok(buffer)
+    }
+
+    /// Decode PNG, BMP or PPM bytes, see [`Image::decode`]
+    pub fn from_image_bytes(bytes: &[u8], format: Format) -> Result<Self, Error> {
+        Self::from_image_bytes_with(bytes, format, LoadOptions::default())
+    }
+
+    /// Like [`Buffer::from_image_bytes`], see [`LoadOptions`]
+    pub fn from_image_bytes_with(bytes: &[u8], format: Format, options: LoadOptions) -> Result<Self, Error> {
+        Self::from_image(&options.apply(Image::decode(bytes)?), format)
+    }
+
+    /// Decode an image file, see [`Image::open`]
+    pub fn load<P: AsRef<std::path::Path>>(path: P, format: Format) -> Result<Self, Error> {
+        Self::load_with(path, format, LoadOptions::default())
+    }
+
+    /// Like [`Buffer::load`], see [`LoadOptions`]
+    pub fn load_with<P: AsRef<std::path::Path>>(path: P, format: Format, options: LoadOptions) -> Result<Self, Error> {
+        Self::from_image(&options.apply(Image::open(path)?), format)
+    }
+
+    /// Read the whole buffer back into CPU memory, call [`Context::finish`] first
+    pub fn to_image(&self) -> Result<Image, Error> {
+        self.data()?;
+        let pixels = (0..self.height())
+            .flat_map(|y| (0..self.width()).map(move |x| self.pixel(x, y).unwrap()))
+            .collect();
+        Image::new(self.width(), self.height(), pixels)
+    }
+
+    /// Encode the buffer contents, see [`Image::encode`]
+    pub fn encode(&self, format: ImageFormat) -> Result<Vec<u8>, Error> {
+        self.encode_with(format, EncodeOptions::default())
+    }
+
+    /// Like [`Buffer::encode`], see [`EncodeOptions`]
+    pub fn encode_with(&self, format: ImageFormat, options: EncodeOptions) -> Result<Vec<u8>, Error> {
+        options.apply(self.to_image()?).encode(format)
+    }
+
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Error> {
+        self.save_png_with(path, EncodeOptions::default())
+    }
+
+    /// Like [`Buffer::save_png`], see [`EncodeOptions`]
+    pub fn save_png_with<P: AsRef<std::path::Path>>(&self, path: P, options: EncodeOptions) -> Result<(), Error> {
+        options.apply(self.to_image()?).save(path, ImageFormat::Png)
+    }
+
+    /// Copy pixels already in the buffer's format, `stride` is the bytes per row of `data`.
+    /// The driver handles the buffer's own stride and alignment
+    pub fn upload(&mut self, data: &[u8], stride: u32) -> Result<(), Error> {
+        self.upload_planes(&[(data, stride)])
+    }
+
+    /// Copy every plane listed by [`Format::planes`], in that order, as `(data, stride)` pairs
+    pub fn upload_planes(&mut self, planes: &[(&[u8], u32)]) -> Result<(), Error> {
+        if self.buffer.memory.is_null() {
+            return Err(Error::NotSupport);
+        }
+        let layouts = self.format.planes(self.width(), self.height());
+        if planes.len() != layouts.len() {
+            return Err(Error::InvalidArgument);
+        }
+        let mut data = [null_mut(); 3];
+        let mut strides = [0; 3];
+        let mut alpha = None;
+        for (layout, &(bytes, stride)) in layouts.iter().zip(planes) {
+            let rows = layout.rows as usize;
+            if stride < layout.row_bytes || (rows > 0 && bytes.len() < stride as usize * (rows - 1) + layout.row_bytes as usize) {
+                return Err(Error::InvalidArgument);
+            }
+            let index = match layout.plane {
+                Plane::Primary => 0,
+                Plane::Chroma => 1,
+                Plane::V => 2,
+                Plane::Alpha => {
+                    alpha = Some((*layout, bytes, stride as usize));
+                    continue;
+                }
+            };
+            data[index] = bytes.as_ptr() as *mut u8;
+            strides[index] = stride;
+        }
+        wrap_result(driver(|| unsafe {
+            vg_lite_upload_buffer(&mut self.buffer, data.as_mut_ptr(), strides.as_mut_ptr())
+        }), ())?;
+        // the driver only knows the address of the alpha plane, copy it by hand
+        if let Some((layout, bytes, stride)) = alpha {
+            let plane = self.planes.iter().find(|(plane, _)| *plane == Plane::Alpha).ok_or(Error::NotSupport)?;
+            let row = layout.row_bytes as usize;
+            for y in 0..layout.rows as usize {
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        bytes[y * stride..].as_ptr(),
+                        (plane.1.memory as *mut u8).add(y * plane.1.stride as usize),
+                        row
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Colors looked up by an indexed buffer, at most `2^bits` of them
+    pub fn set_palette(&mut self, palette: &[Color]) -> Result<(), Error> {
+        if !self.format.is_indexed() || palette.len() > 1 << self.format.bits_per_pixel() {
+            return Err(Error::InvalidArgument);
+        }
+        self.palette = palette.to_vec();
+        Ok(())
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        &self.palette
+    }
+
+    /// Pixel order inside each byte of indexed formats below 8 bits
+    pub fn set_index_endian(&mut self, endian: IndexEndian) {
+        self.buffer.index_endian = endian.into();
+    }
+
+    pub fn index_endian(&self) -> IndexEndian {
+        match self.buffer.index_endian {
+            vg_lite_index_endian_VG_LITE_INDEX_BIG_ENDIAN => IndexEndian::Big,
+            _ => IndexEndian::Little
+        }
     }
 
-    pub fn data(&self) -> &mut [u8] {
-        unsafe {
+    /// Load the palette into the CLUT, the GPU has a single one shared by every indexed source
+    fn bind_clut(&self) -> Result<(), Error> {
+        if !self.format.is_indexed() {
+            return Ok(());
+        }
+        let mut colors = indexed::clut(&self.palette, self.format);
+        wrap_result(driver(|| unsafe {
+            vg_lite_set_CLUT(colors.len() as u32, colors.as_mut_ptr())
+        }), ())
+    }
+
+    /// How the pixels of this buffer combine with the blit color when it is a source
+    pub fn set_image_mode(&mut self, mode: ImageMode) {
+        self.buffer.image_mode = mode.into();
+    }
+
+    pub fn image_mode(&self) -> ImageMode {
+        match self.buffer.image_mode {
+            vg_lite_image_mode_VG_LITE_MULTIPLY_IMAGE_MODE => ImageMode::Multiply,
+            vg_lite_image_mode_VG_LITE_STENCIL_MODE => ImageMode::Stencil,
+            vg_lite_image_mode_VG_LITE_NONE_IMAGE_MODE => ImageMode::None,
+            vg_lite_image_mode_VG_LITE_RECOLOR_MODE => ImageMode::Recolor,
+            _ => ImageMode::Normal
+        }
+    }
+
+    /// [`Transparency::Opaque`] lets the GPU skip blending with the target when this buffer is a source
+    pub fn set_transparency(&mut self, transparency: Transparency) {
+        self.buffer.transparency_mode = transparency.into();
+    }
+
+    pub fn transparency(&self) -> Transparency {
+        match self.buffer.transparency_mode {
+            vg_lite_transparency_VG_LITE_IMAGE_TRANSPARENT => Transparency::Transparent,
+            _ => Transparency::Opaque
+        }
+    }
+
+    /// Conversion used when sampling a YUV buffer
+    pub fn set_yuv_standard(&mut self, standard: YuvStandard) {
+        self.buffer.yuv.yuv2rgb = standard.into();
+    }
+
+    pub fn yuv_standard(&self) -> YuvStandard {
+        match self.buffer.yuv.yuv2rgb {
+            vg_lite_yuv2rgb_VG_LITE_YUV709 => YuvStandard::Bt709,
+            _ => YuvStandard::Bt601
+        }
+    }
+
+    /// Order of interleaved chroma samples of [`Format::NV12`], [`Format::NV16`] and [`Format::YUY2`]
+    pub fn set_uv_swizzle(&mut self, swizzle: UvSwizzle) {
+        self.buffer.yuv.swizzle = swizzle.into();
+    }
+
+    pub fn uv_swizzle(&self) -> UvSwizzle {
+        match self.buffer.yuv.swizzle {
+            vg_lite_swizzle_VG_LITE_SWIZZLE_VU => UvSwizzle::Vu,
+            _ => UvSwizzle::Uv
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.buffer.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.buffer.height as u32
+    }
+
+    /// Bytes from one row to the next, may be larger than `width * bytes_per_pixel`
+    pub fn stride(&self) -> u32 {
+        self.buffer.stride as u32
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Color of the pixel at `(x, y)`, read by the CPU. YUV is converted like the GPU would
+    pub fn pixel(&self, x: u32, y: u32) -> Result<Color, Error> {
+        let data = self.data()?;
+        if self.format.is_indexed() {
+            let row = &data[(y * self.stride()) as usize..];
+            let index = indexed::read_index(row, x, self.format, self.index_endian());
+            return Ok(self.palette.get(index as usize).copied().unwrap_or(Color::TRANSPARENT));
+        }
+        if !self.format.is_yuv() {
+            return Ok(PixelSlice::try_from(self)?.pixel(x, y));
+        }
+        let (x, y) = (x as usize, y as usize);
+        let sample = |plane, x: usize, y: usize| self.plane_data(plane)
+            .map_or(128, |(data, stride)| data[y * stride + x]);
+        let (luma, u, v) = match self.format {
+            Format::YUY2 | Format::AYUY2 => {
+                let pair = x / 2 * 4;
+                (sample(Plane::Primary, x * 2, y), sample(Plane::Primary, pair + 1, y), sample(Plane::Primary, pair + 3, y))
+            }
+            Format::NV12 | Format::ANV12 | Format::NV16 => {
+                let row = if self.format == Format::NV16 { y } else { y / 2 };
+                (sample(Plane::Primary, x, y), sample(Plane::Chroma, x / 2 * 2, row), sample(Plane::Chroma, x / 2 * 2 + 1, row))
+            }
+            Format::YV12 => (sample(Plane::Primary, x, y), sample(Plane::Chroma, x / 2, y / 2), sample(Plane::V, x / 2, y / 2)),
+            _ => (sample(Plane::Primary, x, y), sample(Plane::Chroma, x, y), sample(Plane::V, x, y))
+        };
+        let swapped = self.uv_swizzle() == UvSwizzle::Vu && !matches!(self.format, Format::YV12 | Format::YV24);
+        let (u, v) = if swapped { (v, u) } else { (u, v) };
+        let a = self.plane_data(Plane::Alpha).map_or(255, |(data, stride)| data[y * stride + x]);
+        Ok(Color { a, ..self.yuv_standard().to_rgb(luma, u, v) })
+    }
+
+    /// Memory of the first plane, see [`Format::planes`] for YUV formats.
+    /// [`Error::NotSupport`] without a CPU mapping
+    pub fn data(&self) -> Result<&mut [u8], Error> {
+        if self.buffer.memory.is_null() {
+            return Err(Error::NotSupport);
+        }
+        let row = (self.width() * self.format.bits_per_pixel()).div_ceil(8);
+        // the last row of a view stops at its right edge, the parent may end right after it
+        let len = match self.source {
+            BufferSource::None if self.height() > 0 => self.stride() * (self.height() - 1) + row,
+            _ => self.stride() * self.height()
+        };
+        Ok(unsafe {
             core::slice::from_raw_parts_mut(
                 self.buffer.memory as *mut u8,
-                (self.buffer.stride * self.buffer.height) as usize
+                len as usize
             )
-        }
+        })
+    }
+
+    /// The GPU reads or writes the memory in the open submission, see [`Context::in_flight`]
+    pub(crate) fn used(&self) {
+        self.last_use.set(inflight::submission());
     }
 
     pub fn clear(&mut self, rectangle: Option<&mut Rectangle>, color: Color) -> Result<(), Error> {
-        wrap_result(unsafe {
+        self.used();
+        wrap_result(driver(|| unsafe {
             vg_lite_clear(
                 &mut self.buffer,
                 match rectangle {
@@ -227,7 +786,7 @@ impl Buffer {
                 },
                 color.into()
             )
-        }, ())
+        }), ())
     }
 
     pub fn blit(
@@ -238,16 +797,74 @@ impl Buffer {
         color: Color,
         filter: Filter
     ) -> Result<(), Error> {
-        wrap_result(unsafe {
+        source.bind_clut()?;
+        let filter = filter.bind()?;
+        self.used();
+        source.used();
+        wrap_result(driver(|| unsafe {
             vg_lite_blit(
                 &mut self.buffer,
                 &mut source.buffer,
                 matrix,
                 blend.into(),
                 color.into(),
-                filter.into()
+                filter
             )
-        }, ())
+        }), ())
+    }
+
+    /// Like [`Buffer::blit`] with only `rect` of the source, placed at the origin before `matrix`
+    pub fn blit_rect(
+        &mut self,
+        source: &mut Buffer,
+        rect: &mut Rectangle,
+        matrix: &mut Transform,
+        blend: Blend,
+        color: Color,
+        filter: Filter
+    ) -> Result<(), Error> {
+        source.bind_clut()?;
+        let filter = filter.bind()?;
+        self.used();
+        source.used();
+        wrap_result(driver(|| unsafe {
+            vg_lite_blit_rect(
+                &mut self.buffer,
+                &mut source.buffer,
+                rect,
+                matrix,
+                blend.into(),
+                color.into(),
+                filter
+            )
+        }), ())
+    }
+
+    /// Blit `icon` as a mask filled with `color`, usually an [`Format::A8`] icon.
+    /// Uses stencil mode, or recolor mode on GPUs without stencil support. Recolor comes with
+    /// the LVGL support of the driver, [`Error::NotSupport`] without either
+    pub fn tint_blit(
+        &mut self,
+        icon: &mut Buffer,
+        matrix: &mut Transform,
+        color: Color,
+        filter: Filter
+    ) -> Result<(), Error> {
+        if !icon.format.has_alpha() {
+            return Err(Error::InvalidArgument);
+        }
+        let mode = if has_feature(vg_lite_feature_gcFEATURE_BIT_VG_STENCIL) {
+            ImageMode::Stencil
+        } else if has_feature(vg_lite_feature_gcFEATURE_BIT_VG_LVGL_SUPPORT) {
+            ImageMode::Recolor
+        } else {
+            return Err(Error::NotSupport);
+        };
+        let previous = icon.image_mode();
+        icon.set_image_mode(mode);
+        let result = self.blit(icon, matrix, Blend::SourceOver, color, filter);
+        icon.set_image_mode(previous);
+        result
     }
 
     pub fn draw<T: OpCodeFormat>(
@@ -258,16 +875,19 @@ impl Buffer {
         blend: Blend,
         color: Color
     ) -> Result<(), Error> {
-        wrap_result(unsafe {
+        tess::check_window(self, path.bounding_box(), transform)?;
+        self.used();
+        path.used();
+        wrap_result(driver(|| unsafe {
             vg_lite_draw(
                 &mut self.buffer,
-                &mut path.path,
+                path.raw_mut(),
                 fill_rule.into(),
                 transform,
                 blend.into(),
                 color.into()
             )
-        }, ())
+        }), ())
     }
 
     pub fn draw_pattern<T: OpCodeFormat>(
@@ -282,10 +902,16 @@ impl Buffer {
         color: Color,
         filter: Filter
     ) -> Result<(), Error> {
-        wrap_result(unsafe {
+        tess::check_window(self, path.bounding_box(), path_transform)?;
+        pattern.bind_clut()?;
+        let filter = filter.bind()?;
+        self.used();
+        path.used();
+        pattern.used();
+        wrap_result(driver(|| unsafe {
             vg_lite_draw_pattern(
                 &mut self.buffer,
-                &mut path.path,
+                path.raw_mut(),
                 fill_rule.into(),
                 path_transform,
                 &mut pattern.buffer,
@@ -293,25 +919,81 @@ impl Buffer {
                 blend.into(),
                 pattern_mode.into(),
                 color.into(),
-                filter.into()
+                filter
             )
-        }, ())
+        }), ())
+    }
+
+    /// [`Buffer::blit`] with the source faded by `opacity` on top of the source alpha in force,
+    /// see [`Context::source_global_alpha`]
+    #[allow(clippy::too_many_arguments)]
+    pub fn blit_with_opacity(
+        &mut self,
+        context: &Context,
+        source: &mut Buffer,
+        matrix: &mut Transform,
+        blend: Blend,
+        color: Color,
+        filter: Filter,
+        opacity: u8
+    ) -> Result<(), Error> {
+        let _alpha = GlobalAlphaGuard::opacity(context, opacity)?;
+        self.blit(source, matrix, blend, color, filter)
+    }
+
+    /// [`Buffer::draw_pattern`] with the pattern faded by `opacity`
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_pattern_with_opacity<T: OpCodeFormat>(
+        &mut self,
+        context: &Context,
+        path: &mut Path<T>,
+        fill_rule: Fill,
+        path_transform: &mut Transform,
+        pattern: &mut Buffer,
+        pattern_matrix: &mut Transform,
+        blend: Blend,
+        pattern_mode: PatternMode,
+        color: Color,
+        filter: Filter,
+        opacity: u8
+    ) -> Result<(), Error> {
+        let _alpha = GlobalAlphaGuard::opacity(context, opacity)?;
+        self.draw_pattern(path, fill_rule, path_transform, pattern, pattern_matrix, blend, pattern_mode, color, filter)
     }
 }
 
-impl Drop for Buffer {
+impl Drop for Buffer<'_> {
     fn drop(&mut self) {
-        match self.source {
-            BufferSource::Allocated => unsafe { vg_lite_free(&mut self.buffer); }
-            BufferSource::Mapped => unsafe { vg_lite_unmap(&mut self.buffer); }
-            _ => ()
+        let yuv = &mut self.buffer.yuv;
+        if self.planes.iter().any(|(plane, _)| *plane == Plane::Chroma) {
+            (yuv.uv_planar, yuv.uv_memory, yuv.uv_handle) = (0, null_mut(), null_mut());
+        }
+        if self.planes.iter().any(|(plane, _)| *plane == Plane::V) {
+            (yuv.v_planar, yuv.v_memory, yuv.v_handle) = (0, null_mut(), null_mut());
+        }
+        let planes = self.planes.iter().map(|(_, plane)| *plane).filter(|plane| !plane.handle.is_null()).collect();
+        let last_use = self.last_use.get();
+        let release = match self.source {
+            BufferSource::None => return,
+            BufferSource::Allocated => inflight::Release::Free(self.buffer, planes, self.allocated),
+            BufferSource::Mapped => inflight::Release::Unmap(self.buffer, planes),
+            BufferSource::UserMemory => {
+                // the borrow of the memory ends here, the GPU can't be left writing to it
+                if inflight::in_flight(last_use) {
+                    let _ = inflight::finish();
+                }
+                inflight::Release::Unmap(self.buffer, planes)
+            }
         };
+        inflight::release(last_use, release);
     }
 }
 
 #[derive(Clone, Copy)]
 pub enum Filter {
-    Pointer, Linear, Bilinear
+    Pointer, Linear, Bilinear,
+    /// 3x3 convolution of the source, see [`blur`] for larger radii
+    Gaussian(GaussianWeights)
 }
 
 impl Into<vg_lite_filter> for Filter {
@@ -319,7 +1001,8 @@ impl Into<vg_lite_filter> for Filter {
         match self {
             Self::Pointer => vg_lite_filter_VG_LITE_FILTER_POINT,
             Self::Linear => vg_lite_filter_VG_LITE_FILTER_LINEAR,
-            Self::Bilinear => vg_lite_filter_VG_LITE_FILTER_BI_LINEAR
+            Self::Bilinear => vg_lite_filter_VG_LITE_FILTER_BI_LINEAR,
+            Self::Gaussian(_) => vg_lite_filter_VG_LITE_FILTER_GAUSSIAN
         }
     }
 }
@@ -336,6 +1019,38 @@ impl Into<vg_lite_blend> for Blend {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageMode {
+    /// Pixels as they are
+    Normal = vg_lite_image_mode_VG_LITE_NORMAL_IMAGE_MODE as isize,
+    /// Pixels multiplied by the blit color
+    Multiply = vg_lite_image_mode_VG_LITE_MULTIPLY_IMAGE_MODE as isize,
+    /// Alpha of the pixels applied to the blit color
+    Stencil = vg_lite_image_mode_VG_LITE_STENCIL_MODE as isize,
+    /// Pixels ignored, only the blit color is drawn
+    None = vg_lite_image_mode_VG_LITE_NONE_IMAGE_MODE as isize,
+    /// Color of the pixels replaced by the blit color, their alpha kept
+    Recolor = vg_lite_image_mode_VG_LITE_RECOLOR_MODE as isize,
+}
+
+impl Into<vg_lite_image_mode> for ImageMode {
+    fn into(self) -> vg_lite_image_mode {
+        self as vg_lite_image_mode
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    Opaque = vg_lite_transparency_VG_LITE_IMAGE_OPAQUE as isize,
+    Transparent = vg_lite_transparency_VG_LITE_IMAGE_TRANSPARENT as isize,
+}
+
+impl Into<vg_lite_transparency> for Transparency {
+    fn into(self) -> vg_lite_transparency {
+        self as vg_lite_transparency
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum PatternMode {
     Color = vg_lite_pattern_mode_VG_LITE_PATTERN_COLOR as isize,
@@ -354,12 +1069,1385 @@ impl Into<vg_lite_pattern_mode> for PatternMode {
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use std::{cell::RefCell, rc::Rc};
+
+    /// The driver keeps a single process wide context, tests using the GPU must not overlap
+    static GPU: Mutex<()> = Mutex::new(());
+
+    fn context(tess_width: u32, tess_height: u32) -> (MutexGuard<'static, ()>, Context) {
+        let guard = GPU.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        (guard, Context::new(tess_width, tess_height).unwrap())
+    }
+
+    /// `None` for formats the GPU can't render to
+    fn allocate(width: u32, height: u32, format: Format) -> Option<Buffer<'static>> {
+        match Buffer::allocate(width, height, format) {
+            Err(Error::NotSupport) => None,
+            buffer => Some(buffer.unwrap())
+        }
+    }
 
     #[test]
     fn init_and_deinit() {
-        let ctx = Context::new(640, 480).unwrap();
+        let (_gpu, ctx) = context(640, 480);
         let mut buffer = Buffer::allocate(640, 480, Format::BGRA8888).unwrap();
         buffer.clear(None, Color { r: 0, g: 0, b: 0, a: 0 }).unwrap();
         ctx.finish().unwrap();
     }
+
+    #[test]
+    fn color_encoding() {
+        let color = Color::rgba(0x11, 0x22, 0x33, 0x44);
+        assert_eq!(u32::from(color), 0x44332211);
+        assert_eq!(color.pack(Format::RGBA8888), 0x44332211);
+        assert_eq!(color.pack(Format::BGRA8888), 0x44112233);
+        let red = Color::rgb(255, 0, 0);
+        assert_eq!(red.pack(Format::RGB565), 0x001f);
+        assert_eq!(red.pack(Format::BGR565), 0xf800);
+        for format in Format::ALL.into_iter().filter(|f| !matches!(f, Format::A8 | Format::L8)) {
+            for color in [Color::BLACK, Color::WHITE, red, Color::rgb(0, 255, 0), Color::rgb(0, 0, 255)] {
+                assert_eq!(Color::unpack(color.pack(format), format), color, "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn color_conversion() {
+        assert_eq!(Color::default(), Color::TRANSPARENT);
+        assert_eq!(Color::from_f32(1., 0.5, 0., 2.), Color::rgba(255, 128, 0, 255));
+        assert_eq!(Color::rgba(255, 128, 0, 255).to_f32(), [1., 128. / 255., 0., 1.]);
+        let color = Color::rgba(200, 100, 50, 128);
+        assert_eq!(color.premultiply(), Color::rgba(100, 50, 25, 128));
+        assert_eq!(color.premultiply().unpremultiply(), Color::rgba(199, 100, 50, 128));
+        assert_eq!(Color::rgba(10, 20, 30, 0).unpremultiply(), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn color_parsing() {
+        assert_eq!(Color::from_hex("#ff8000").unwrap(), Color::rgb(255, 128, 0));
+        assert_eq!(Color::from_hex("ff800080").unwrap(), Color::rgba(255, 128, 0, 128));
+        assert_eq!(Color::from_hex("#f80").unwrap(), Color::rgb(255, 136, 0));
+        assert_eq!(Color::from_hex("#f808").unwrap(), Color::rgba(255, 136, 0, 136));
+        assert!(Color::from_hex("#ff80").is_ok());
+        assert!(Color::from_hex("#ff800").is_err());
+        assert!(Color::from_hex("#gg8000").is_err());
+        assert!(Color::from_hex("#ff80é").is_err());
+        assert_eq!("rgb(255, 128, 0)".parse::<Color>().unwrap(), Color::rgb(255, 128, 0));
+        assert_eq!("rgba(255, 128, 0, 0.5)".parse::<Color>().unwrap(), Color::rgba(255, 128, 0, 128));
+        assert_eq!("rgb(100% 50% 0% / 25%)".parse::<Color>().unwrap(), Color::rgba(255, 128, 0, 64));
+        assert_eq!(" Magenta ".parse::<Color>().unwrap(), Color::rgb(255, 0, 255));
+        assert_eq!("transparent".parse::<Color>().unwrap(), Color::TRANSPARENT);
+        assert!("rgb(1, 2)".parse::<Color>().is_err());
+        assert!("rgb(1, 2, 3".parse::<Color>().is_err());
+        assert!("notacolor".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn clear_every_format() {
+        let (_gpu, ctx) = context(64, 64);
+        let colors = [
+            Color::BLACK, Color::WHITE,
+            Color::rgb(255, 0, 0), Color::rgb(0, 255, 0), Color::rgb(0, 0, 255),
+            Color::rgba(0x80, 0x40, 0xc0, 0xff)
+        ];
+        for format in Format::ALL {
+            let Some(mut buffer) = allocate(16, 16, format) else { continue };
+            for color in colors {
+                buffer.clear(None, Color::TRANSPARENT).unwrap();
+                buffer.clear(Some(&mut Rectangle { x: 4, y: 4, width: 8, height: 8 }), color).unwrap();
+                ctx.finish().unwrap();
+                let expected = Color::unpack(color.pack(format), format);
+                assert_eq!(buffer.pixel(4, 4).unwrap(), expected, "{:?} {:?}", format, color);
+                assert_eq!(buffer.pixel(11, 11).unwrap(), expected, "{:?} {:?}", format, color);
+                assert_eq!(buffer.pixel(3, 3).unwrap(), Color::unpack(Color::TRANSPARENT.pack(format), format));
+            }
+        }
+    }
+
+    fn bmp(bits: u16, palette: &[[u8; 4]], rows: &[&[u8]]) -> Vec<u8> {
+        let width = 2u32;
+        let header = 14 + 40 + palette.len() as u32 * 4;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(header.to_le_bytes());
+        bytes.extend(40u32.to_le_bytes());
+        bytes.extend(width.to_le_bytes());
+        bytes.extend((rows.len() as i32).to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(bits.to_le_bytes());
+        bytes.extend([0; 12]);
+        bytes.extend([0; 4]);
+        bytes.extend((palette.len() as u32).to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        palette.iter().for_each(|c| bytes.extend(c));
+        // stored bottom up, rows padded to 4 bytes
+        for row in rows.iter().rev() {
+            bytes.extend(*row);
+            bytes.extend(vec![0; (4 - row.len() % 4) % 4]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decode_images() {
+        let (_gpu, _ctx) = context(64, 64);
+        let expected = Image::new(2, 2, vec![
+            Color::rgb(255, 0, 0), Color::rgb(0, 255, 0),
+            Color::rgb(0, 0, 255), Color::WHITE
+        ]).unwrap();
+
+        let ppm = b"P6\n# comment\n2 2\n255\n\xff\0\0\0\xff\0\0\0\xff\xff\xff\xff";
+        assert_eq!(ImageFormat::detect(ppm), Some(ImageFormat::Ppm));
+        assert_eq!(Image::decode(ppm).unwrap(), expected);
+        let ascii = b"P3 2 2 15 15 0 0  0 15 0  0 0 15  15 15 15";
+        assert_eq!(Image::decode(ascii).unwrap(), expected);
+
+        let bgr = bmp(24, &[], &[&[0, 0, 255, 0, 255, 0], &[255, 0, 0, 255, 255, 255]]);
+        assert_eq!(ImageFormat::detect(&bgr), Some(ImageFormat::Bmp));
+        assert_eq!(Image::decode(&bgr).unwrap(), expected);
+        let palette = [[0, 0, 255, 0], [0, 255, 0, 0], [255, 0, 0, 0], [255, 255, 255, 0]];
+        assert_eq!(Image::decode(&bmp(8, &palette, &[&[0, 1], &[2, 3]])).unwrap(), expected);
+        assert_eq!(Image::decode(&bmp(4, &palette, &[&[0x01], &[0x23]])).unwrap(), expected);
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[
+            255, 0, 0, 255, 0, 255, 0, 255,
+            0, 0, 255, 255, 255, 255, 255, 128
+        ]).unwrap();
+        writer.finish().unwrap();
+        let image = Image::decode(&png).unwrap();
+        assert_eq!(&image.pixels()[..3], &expected.pixels()[..3]);
+        assert_eq!(image.pixel(1, 1), Color::rgba(255, 255, 255, 128));
+        assert_eq!(image.premultiplied().pixel(1, 1), Color::rgba(128, 128, 128, 128));
+        let straight = Buffer::from_image_bytes(&png, Format::RGBA8888).unwrap();
+        assert_eq!(straight.pixel(1, 1).unwrap(), Color::rgba(255, 255, 255, 128));
+        let premultiply = LoadOptions { premultiply: true };
+        let path = std::env::temp_dir().join(format!("vglite-load-{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        let loaded = Buffer::load_with(&path, Format::RGBA8888, premultiply);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().pixel(1, 1).unwrap(), Color::rgba(128, 128, 128, 128));
+        let premultiplied = Buffer::from_image_bytes_with(&png, Format::RGBA8888, premultiply).unwrap();
+        assert_eq!(premultiplied.pixel(1, 1).unwrap(), Color::rgba(128, 128, 128, 128));
+
+        assert!(matches!(Image::decode(b"GIF89a"), Err(Error::NotSupport)));
+        assert!(matches!(Image::decode(&ppm[..20]), Err(Error::InvalidImage)));
+        assert!(matches!(Image::decode(&bgr[..40]), Err(Error::InvalidImage)));
+
+        // header dimensions far beyond the data
+        let mut huge = bgr.clone();
+        huge[18..26].copy_from_slice(&[0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff, 0x7f]);
+        assert!(matches!(Image::decode(&huge), Err(Error::InvalidImage)));
+        assert!(matches!(Image::decode(b"P6\n4000000000 4000000000\n255\n\0\0\0"), Err(Error::InvalidImage)));
+        assert!(matches!(Image::decode(b"P3\n4000000000 4000000000\n255\n0 0 0"), Err(Error::InvalidImage)));
+        assert!(matches!(Image::decode(b"P5\n1 1\n70000\n\0\0"), Err(Error::InvalidImage)));
+        // 32 bit wide red mask, the masks sit where the palette would
+        let mut wide = bmp(32, &[[0xff; 4], [0; 4], [0; 4]], &[&[0x80, 0, 0, 0x80, 0xff, 0xff, 0xff, 0xff]]);
+        wide[30..34].copy_from_slice(&3u32.to_le_bytes());
+        let image = Image::decode(&wide).unwrap();
+        assert_eq!((image.pixel(0, 0), image.pixel(1, 0)), (Color::rgb(128, 0, 0), Color::rgb(255, 0, 0)));
+    }
+
+    #[test]
+    fn upload_image() {
+        let (_gpu, ctx) = context(64, 64);
+        let pixels = (0..35 * 3).map(|i| Color::rgb(i as u8, 0, 255 - i as u8)).collect();
+        let image = Image::new(35, 3, pixels).unwrap();
+        for format in Format::ALL {
+            let buffer = match Buffer::from_image(&image, format) {
+                Err(Error::NotSupport) => continue,
+                buffer => buffer.unwrap()
+            };
+            ctx.finish().unwrap();
+            assert!(buffer.stride() >= 35 * format.bpp());
+            for (x, y) in [(0, 0), (34, 0), (17, 1), (34, 2)] {
+                let color = image.pixel(x, y);
+                assert_eq!(buffer.pixel(x, y).unwrap(), Color::unpack(color.pack(format), format));
+            }
+        }
+    }
+
+    #[test]
+    fn encode_images() {
+        let image = Image::new(3, 2, vec![
+            Color::rgb(255, 0, 0), Color::rgb(0, 255, 0), Color::rgb(0, 0, 255),
+            Color::WHITE, Color::rgba(10, 20, 30, 40), Color::TRANSPARENT
+        ]).unwrap();
+        for format in [ImageFormat::Png, ImageFormat::Bmp] {
+            let bytes = image.encode(format).unwrap();
+            assert_eq!(ImageFormat::detect(&bytes), Some(format));
+            assert_eq!(Image::decode(&bytes).unwrap(), image);
+        }
+        let ppm = image.encode(ImageFormat::Ppm).unwrap();
+        let opaque: Vec<Color> = image.pixels().iter().map(|c| Color { a: 255, ..*c }).collect();
+        assert_eq!(Image::decode(&ppm).unwrap().pixels(), opaque.as_slice());
+
+        let premultiplied = Color::rgba(100, 50, 25, 128);
+        let image = Image::new(1, 1, vec![premultiplied]).unwrap();
+        assert_eq!(image.unpremultiplied().pixel(0, 0), premultiplied.unpremultiply());
+    }
+
+    #[test]
+    fn export_buffer() {
+        let (_gpu, ctx) = context(64, 64);
+        for format in Format::ALL {
+            let Some(mut buffer) = allocate(33, 5, format) else { continue };
+            buffer.clear(None, Color::BLACK).unwrap();
+            buffer.clear(Some(&mut Rectangle { x: 30, y: 1, width: 3, height: 4 }), Color::rgb(255, 0, 0)).unwrap();
+            ctx.finish().unwrap();
+            let image = Image::decode(&buffer.encode(ImageFormat::Png).unwrap()).unwrap();
+            let [black, red] = [Color::BLACK, Color::rgb(255, 0, 0)].map(|c| Color::unpack(c.pack(format), format));
+            assert_eq!((image.width(), image.height()), (33, 5));
+            assert_eq!(image.pixel(29, 1), black);
+            assert_eq!(image.pixel(30, 1), red);
+            assert_eq!(image.pixel(32, 4), red);
+            assert_eq!(image.pixel(32, 0), black);
+        }
+
+        let premultiplied = Color::rgba(100, 50, 25, 128);
+        let buffer = Buffer::from_image(&Image::new(1, 1, vec![premultiplied]).unwrap(), Format::RGBA8888).unwrap();
+        let unpremultiply = EncodeOptions { unpremultiply: true };
+        let png = buffer.encode_with(ImageFormat::Png, unpremultiply).unwrap();
+        assert_eq!(Image::decode(&png).unwrap().pixel(0, 0), premultiplied.unpremultiply());
+        let path = std::env::temp_dir().join(format!("vglite-save-{}.png", std::process::id()));
+        buffer.save_png_with(&path, unpremultiply).unwrap();
+        let saved = Image::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.unwrap().pixel(0, 0), premultiplied.unpremultiply());
+        assert_eq!(Image::decode(&buffer.encode(ImageFormat::Png).unwrap()).unwrap().pixel(0, 0), premultiplied);
+    }
+
+    #[test]
+    fn convert_every_pair() {
+        let mut colors: Vec<Color> = (0..=255).step_by(17).map(|l| Color::rgba(l, l, l, 255 - l)).collect();
+        colors.extend([Color::rgba(255, 0, 0, 255), Color::rgba(12, 200, 77, 100), Color::rgba(0, 0, 255, 0)]);
+        for src_format in Format::ALL {
+            // start from colors representable in the source format
+            let data: Vec<u8> = colors.iter()
+                .flat_map(|c| c.pack(src_format).to_le_bytes()[..src_format.bpp() as usize].to_vec())
+                .collect();
+            let src = PixelSlice::new(&data, colors.len() as u32, 1, src_format).unwrap();
+            for dst_format in Format::ALL {
+                let there = convert(src, dst_format, ConvertOptions::default()).unwrap();
+                let there = PixelSlice::new(&there, colors.len() as u32, 1, dst_format).unwrap();
+                let back = convert(there, src_format, ConvertOptions::default()).unwrap();
+                let back = PixelSlice::new(&back, colors.len() as u32, 1, src_format).unwrap();
+                let (src_bits, dst_bits) = (src_format.channel_bits(), dst_format.channel_bits());
+                for x in 0..colors.len() as u32 {
+                    let (original, result) = (src.pixel(x, 0), back.pixel(x, 0));
+                    let gray = original.r == original.g && original.g == original.b;
+                    let channels = [
+                        (original.r, result.r), (original.g, result.g),
+                        (original.b, result.b), (original.a, result.a)
+                    ];
+                    for (i, (original, result)) in channels.into_iter().enumerate() {
+                        let color = i < 3;
+                        if src_bits[i] == 0 || dst_bits[i] == 0
+                            || (color && dst_format == Format::L8 && !gray) {
+                            continue;
+                        }
+                        // exact when the destination is at least as precise, otherwise within one step
+                        let tolerance = if dst_bits[i] >= src_bits[i] { 0 } else { 255 >> dst_bits[i] };
+                        assert!(
+                            (original as i32 - result as i32).abs() <= tolerance,
+                            "{:?} -> {:?} channel {} {} != {}", src_format, dst_format, i, original, result
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn convert_options() {
+        let data = [0x10, 0x20, 0x30, 0x40, 0xff, 0xff, 0xff, 0xff];
+        let src = PixelSlice::with_stride(&data, 1, 2, 4, Format::RGBA8888).unwrap();
+        assert!(PixelSlice::with_stride(&data, 2, 2, 4, Format::RGBA8888).is_err());
+        assert_eq!(convert(src, Format::RGBA8888, ConvertOptions::default()).unwrap(), data);
+        assert_eq!(convert(src, Format::BGRA8888, ConvertOptions::default()).unwrap(), [0x30, 0x20, 0x10, 0x40, 0xff, 0xff, 0xff, 0xff]);
+        let premultiply = ConvertOptions { alpha: AlphaMode::Premultiply, ..Default::default() };
+        let premultiplied = convert(src, Format::RGBA8888, premultiply).unwrap();
+        assert_eq!(premultiplied, [0x04, 0x08, 0x0c, 0x40, 0xff, 0xff, 0xff, 0xff]);
+        let unpremultiply = ConvertOptions { alpha: AlphaMode::Unpremultiply, ..Default::default() };
+        let straight = PixelSlice::new(&premultiplied, 1, 2, Format::RGBA8888).unwrap();
+        assert_eq!(convert(straight, Format::RGBA8888, unpremultiply).unwrap(), [0x10, 0x20, 0x30, 0x40, 0xff, 0xff, 0xff, 0xff]);
+
+        // a flat color between two RGB565 levels averages out when dithered
+        let gray: Vec<u8> = [0x88u8, 0x88, 0x88, 0xff].repeat(16);
+        let src = PixelSlice::new(&gray, 4, 4, Format::RGBA8888).unwrap();
+        let plain = convert(src, Format::BGR565, ConvertOptions::default()).unwrap();
+        let dithered = convert(src, Format::BGR565, ConvertOptions { dither: true, ..Default::default() }).unwrap();
+        let red = |data: &[u8]| -> Vec<u8> {
+            let pixels = PixelSlice::new(data, 4, 4, Format::BGR565).unwrap();
+            (0..16).map(|i| pixels.pixel(i % 4, i / 4).r).collect()
+        };
+        assert!(red(&plain).iter().all(|&r| r == 0x8c));
+        let dithered = red(&dithered);
+        assert!(dithered.contains(&0x84) && dithered.contains(&0x8c));
+        let mean = dithered.iter().map(|&r| r as u32).sum::<u32>() as f32 / 16.;
+        assert!((mean - 136.).abs() < 1., "{}", mean);
+        // exact levels are stable under dithering
+        let level: Vec<u8> = [0x84u8, 0x82, 0x84, 0xff].repeat(16);
+        let src = PixelSlice::new(&level, 4, 4, Format::RGBA8888).unwrap();
+        assert_eq!(
+            convert(src, Format::BGR565, ConvertOptions { dither: true, ..Default::default() }).unwrap(),
+            convert(src, Format::BGR565, ConvertOptions::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn yuv_conversion() {
+        for standard in [YuvStandard::Bt601, YuvStandard::Bt709] {
+            assert_eq!(standard.to_rgb(235, 128, 128), Color::WHITE);
+            assert_eq!(standard.to_rgb(16, 128, 128), Color::BLACK);
+            assert_eq!(standard.from_rgb(Color::WHITE), [235, 128, 128]);
+            for color in [Color::rgb(255, 0, 0), Color::rgb(0, 255, 0), Color::rgb(0, 0, 255), Color::rgb(200, 100, 50)] {
+                let [y, u, v] = standard.from_rgb(color);
+                let back = standard.to_rgb(y, u, v);
+                for (a, b) in [(back.r, color.r), (back.g, color.g), (back.b, color.b)] {
+                    assert!(a.abs_diff(b) <= 2, "{:?} {:?} {:?}", standard, color, back);
+                }
+            }
+        }
+        assert_eq!(YuvStandard::Bt601.from_rgb(Color::rgb(255, 0, 0)), [82, 90, 240]);
+        assert_eq!(YuvStandard::Bt709.from_rgb(Color::rgb(255, 0, 0)), [63, 102, 240]);
+        assert_eq!(Format::NV12.planes(5, 3)[1], PlaneLayout { plane: Plane::Chroma, row_bytes: 6, rows: 2 });
+        assert_eq!(Format::YV12.planes(5, 3).len(), 3);
+        assert_eq!(Format::AYUY2.planes(5, 3)[0].row_bytes, 12);
+        assert!(convert(PixelSlice::new(&[0; 16], 4, 4, Format::NV12).unwrap(), Format::RGBA8888, ConvertOptions::default()).is_err());
+    }
+
+    /// Planes of a `width * height` frame of `format` made of 2x2 blocks of one color each,
+    /// so that chroma subsampling is lossless, with alpha `x * 40 + y`
+    fn yuv_frame(format: Format, width: u32, height: u32, standard: YuvStandard) -> (Vec<Vec<u8>>, impl Fn(u32, u32) -> Color) {
+        let block = |x: u32, y: u32| {
+            let (x, y) = ((x / 2) as u8, (y / 2) as u8);
+            Color::rgb(40 + x * 60, 200 - y * 80, 30 + (x + y) * 50)
+        };
+        let yuv = move |x: u32, y: u32| standard.from_rgb(block(x, y));
+        let planes = format.planes(width, height).iter().map(|layout| {
+            let mut bytes = Vec::new();
+            for row in 0..layout.rows {
+                for i in 0..layout.row_bytes {
+                    bytes.push(match (layout.plane, format) {
+                        (Plane::Primary, Format::YUY2 | Format::AYUY2) => match i % 4 {
+                            0 | 2 => yuv(i / 2, row)[0],
+                            1 => yuv(i / 4 * 2, row)[1],
+                            _ => yuv(i / 4 * 2, row)[2]
+                        },
+                        (Plane::Primary, _) => yuv(i, row)[0],
+                        (Plane::Alpha, _) => (i * 40 + row) as u8,
+                        (Plane::Chroma, Format::NV12 | Format::ANV12) => yuv(i / 2 * 2, row * 2)[1 + i as usize % 2],
+                        (Plane::Chroma, Format::NV16) => yuv(i / 2 * 2, row)[1 + i as usize % 2],
+                        (Plane::Chroma, Format::YV12) => yuv(i * 2, row * 2)[1],
+                        (Plane::V, Format::YV12) => yuv(i * 2, row * 2)[2],
+                        (Plane::Chroma, _) => yuv(i, row)[1],
+                        (Plane::V, _) => yuv(i, row)[2]
+                    });
+                }
+            }
+            bytes
+        }).collect();
+        (planes, move |x, y| block(x, y))
+    }
+
+    #[test]
+    fn upload_yuv() {
+        let (_gpu, ctx) = context(64, 64);
+        let (width, height) = (6, 3);
+        for format in Format::YUV {
+            for standard in [YuvStandard::Bt601, YuvStandard::Bt709] {
+                let Some(mut frame) = allocate(width, height, format) else { continue };
+                frame.set_yuv_standard(standard);
+                let (planes, expected) = yuv_frame(format, width, height, standard);
+                let layouts = format.planes(width, height);
+                let planes: Vec<_> = planes.iter().zip(&layouts).map(|(p, l)| (p.as_slice(), l.row_bytes)).collect();
+                assert!(frame.upload(planes[0].0, planes[0].1).is_err() || planes.len() == 1);
+                frame.upload_planes(&planes).unwrap();
+                for (x, y) in [(0, 0), (1, 1), (2, 0), (5, 2), (3, 1)] {
+                    let color = frame.pixel(x, y).unwrap();
+                    let expected = expected(x, y);
+                    for (a, b) in [(color.r, expected.r), (color.g, expected.g), (color.b, expected.b)] {
+                        assert!(a.abs_diff(b) <= 2, "{:?} {:?} ({}, {}) {:?}", format, standard, x, y, color);
+                    }
+                    let alpha = if format.has_alpha() { (x * 40 + y) as u8 } else { 255 };
+                    assert_eq!(color.a, alpha, "{:?}", format);
+                }
+
+                let Some(mut target) = allocate(8, 8, Format::BGRA8888) else { continue };
+                target.clear(None, Color::BLACK).unwrap();
+                let mut matrix = Transform::default();
+                matrix.translate(2., 1.);
+                match target.blit(&mut frame, &mut matrix, Blend::None, Color::WHITE, Filter::Pointer) {
+                    Err(Error::NotSupport) => continue,
+                    result => result.unwrap()
+                }
+                ctx.finish().unwrap();
+                for (x, y) in [(0, 0), (1, 1), (4, 2), (3, 1)] {
+                    let color = target.pixel(x + 2, y + 1).unwrap();
+                    let expected = frame.pixel(x, y).unwrap();
+                    for (a, b) in [(color.r, expected.r), (color.g, expected.g), (color.b, expected.b)] {
+                        assert!(a.abs_diff(b) <= 3, "{:?} {:?} ({}, {}) {:?}", format, standard, x, y, color);
+                    }
+                }
+                assert_eq!(target.pixel(0, 0).unwrap(), Color::BLACK);
+            }
+        }
+    }
+
+    #[test]
+    fn indexed_packing() {
+        let palette = |n: usize| (0..n).map(|i| Color::rgb(i as u8, 0, 255 - i as u8)).collect::<Vec<_>>();
+        let image = IndexedImage::new(2, 1, Format::Index4, palette(16), vec![1, 2]).unwrap();
+        assert_eq!(image.pack(IndexEndian::Little), [0x21]);
+        assert_eq!(image.pack(IndexEndian::Big), [0x12]);
+        let image = IndexedImage::new(3, 1, Format::Index1, palette(2), vec![1, 0, 1]).unwrap();
+        assert_eq!(image.pack(IndexEndian::Little), [0b101]);
+        assert_eq!(image.pack(IndexEndian::Big), [0b1010_0000]);
+        for format in Format::INDEXED {
+            let colors = 1 << format.bits_per_pixel();
+            let indices = (0..15).map(|i| (i * 7 % colors) as u8).collect();
+            let image = IndexedImage::new(5, 3, format, palette(colors), indices).unwrap();
+            for endian in [IndexEndian::Little, IndexEndian::Big] {
+                let packed = image.pack(endian);
+                assert_eq!(packed.len(), (5 * format.bits_per_pixel() as usize).div_ceil(8) * 3);
+                let unpacked = IndexedImage::from_packed(5, 3, format, endian, palette(colors), &packed).unwrap();
+                assert_eq!(unpacked, image, "{:?} {:?}", format, endian);
+            }
+        }
+        assert!(IndexedImage::new(1, 1, Format::Index1, palette(3), vec![0]).is_err());
+        assert!(IndexedImage::new(1, 1, Format::Index2, palette(2), vec![2]).is_err());
+        assert!(IndexedImage::new(1, 1, Format::A8, palette(2), vec![0]).is_err());
+    }
+
+    #[test]
+    fn quantize() {
+        let few = vec![Color::WHITE, Color::rgba(255, 0, 0, 128), Color::WHITE, Color::TRANSPARENT];
+        let image = Image::new(2, 2, few).unwrap();
+        let indexed = IndexedImage::quantize(&image, 4).unwrap();
+        assert_eq!(indexed.format(), Format::Index2);
+        assert_eq!(indexed.palette().len(), 3);
+        assert_eq!(indexed.to_image(), image);
+        assert!(IndexedImage::quantize(&image, 3).is_err());
+
+        let ramp = Image::new(256, 1, (0..=255).map(|g| Color::rgb(g, g, g)).collect()).unwrap();
+        let two = IndexedImage::quantize(&ramp, 2).unwrap();
+        assert_eq!(two.palette().len(), 2);
+        assert_ne!(two.index(0, 0), two.index(255, 0));
+        assert_eq!(two.index(0, 0), two.index(100, 0));
+
+        let gradient = Image::new(32, 32, (0..32 * 32)
+            .map(|i| Color::rgb((i % 32 * 8) as u8, (i / 32 * 8) as u8, 128))
+            .collect()).unwrap();
+        for colors in [16, 256] {
+            let indexed = IndexedImage::quantize(&gradient, colors).unwrap();
+            assert!(indexed.palette().len() <= colors);
+            let error = gradient.pixels().iter().zip(indexed.to_image().pixels())
+                .map(|(a, b)| a.r.abs_diff(b.r) as u32 + a.g.abs_diff(b.g) as u32)
+                .sum::<u32>() as f32 / (32. * 32.);
+            assert!(error < if colors == 16 { 40. } else { 10. }, "{} {}", colors, error);
+        }
+    }
+
+    #[test]
+    fn blit_indexed() {
+        let (_gpu, ctx) = context(64, 64);
+        let palette = vec![Color::TRANSPARENT, Color::rgb(255, 0, 0), Color::rgb(0, 0, 255), Color::WHITE];
+        let indices: Vec<u8> = (0..7 * 3).map(|i| (i % 4) as u8).collect();
+        for format in [Format::Index2, Format::Index8] {
+            for endian in [IndexEndian::Little, IndexEndian::Big] {
+                let image = IndexedImage::new(7, 3, format, palette.clone(), indices.clone()).unwrap();
+                let mut source = match Buffer::from_indexed(&image, endian) {
+                    Err(Error::NotSupport) => continue,
+                    source => source.unwrap()
+                };
+                assert_eq!(source.to_image().unwrap(), image.to_image(), "{:?} {:?}", format, endian);
+                let mut target = Buffer::allocate(8, 4, Format::RGBA8888).unwrap();
+                target.clear(None, Color::BLACK).unwrap();
+                let mut matrix = Transform::default();
+                target.blit(&mut source, &mut matrix, Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+                ctx.finish().unwrap();
+                for (x, y) in [(1, 0), (2, 1), (6, 2)] {
+                    assert_eq!(target.pixel(x, y).unwrap(), image.pixel(x, y), "{:?} {:?}", format, endian);
+                }
+            }
+        }
+        let mut buffer = Buffer::allocate(4, 4, Format::RGBA8888).unwrap();
+        assert!(buffer.set_palette(&palette).is_err());
+    }
+
+    #[test]
+    fn map_user_memory() {
+        let (_gpu, ctx) = context(64, 64);
+        let (width, height, stride) = (10, 4, 64);
+        assert_eq!(Buffer::mapped_size(width, height, stride, Format::RGBA8888).unwrap(), 256);
+        assert_eq!(Buffer::mapped_size(4, 2, 4, Format::NV12).unwrap(), 12);
+        let mut memory = vec![0u8; 256];
+        assert!(Buffer::map_user_memory(&mut memory, width, height, 39, Format::RGBA8888).is_err());
+        assert!(Buffer::map_user_memory(&mut memory[..255], width, height, stride, Format::RGBA8888).is_err());
+        let mut padded = vec![0u8; 4 * 72];
+        let unaligned = Buffer::map_user_memory(&mut padded, width, height, 72, Format::RGBA8888);
+        assert!(matches!(unaligned, Err(Error::InvalidArgument)));
+        {
+            let mut buffer = Buffer::map_user_memory(&mut memory, width, height, stride, Format::RGBA8888).unwrap();
+            assert_eq!(buffer.stride(), stride);
+            buffer.clear(Some(&mut Rectangle { x: 1, y: 2, width: 2, height: 1 }), Color::rgb(1, 2, 3)).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(buffer.pixel(1, 2).unwrap(), Color::rgb(1, 2, 3));
+            buffer.flush_mapped().unwrap();
+        }
+        assert_eq!(&memory[2 * 64 + 4..][..8], &[1, 2, 3, 255, 1, 2, 3, 255]);
+        assert!(memory[2 * 64 + 12..].iter().all(|&b| b == 0));
+
+        let mut owned = Buffer::allocate(4, 4, Format::RGBA8888).unwrap();
+        assert!(owned.flush_mapped().is_err());
+
+        // without a CPU mapping the pixels are only reachable by the GPU
+        let mut dmabuf = unsafe { Buffer::map_dmabuf(-1, 0, 16, 4, 64, Format::RGBA8888, std::ptr::null_mut()) }.unwrap();
+        assert!(matches!(dmabuf.data(), Err(Error::NotSupport)));
+        assert!(matches!(dmabuf.pixel(0, 0), Err(Error::NotSupport)));
+        assert!(matches!(dmabuf.to_image(), Err(Error::NotSupport)));
+        assert!(matches!(dmabuf.upload(&[0; 64 * 4], 64), Err(Error::NotSupport)));
+        assert!(matches!(dmabuf.region(Rectangle { x: 0, y: 1, width: 4, height: 2 }).unwrap().data(), Err(Error::NotSupport)));
+    }
+
+    #[test]
+    fn buffer_region() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut atlas = Buffer::allocate(64, 64, Format::RGBA8888).unwrap();
+        atlas.clear(None, Color::BLACK).unwrap();
+        let stride = atlas.stride() as i32;
+        let aligned_x = ADDRESS_ALIGNMENT as i32 / 4;
+        let rect = Rectangle { x: aligned_x, y: 3, width: 16, height: 8 };
+        {
+            let mut icon = atlas.region(rect).unwrap();
+            assert_eq!((icon.width(), icon.height(), icon.stride()), (16, 8, stride as u32));
+            icon.clear(None, Color::rgb(255, 0, 0)).unwrap();
+            icon.clear(Some(&mut Rectangle { x: 0, y: 0, width: 1, height: 1 }), Color::WHITE).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(icon.pixel(0, 0).unwrap(), Color::WHITE);
+        }
+        assert_eq!(atlas.pixel(aligned_x as u32, 3).unwrap(), Color::WHITE);
+        assert_eq!(atlas.pixel(aligned_x as u32 + 15, 10).unwrap(), Color::rgb(255, 0, 0));
+        assert_eq!(atlas.pixel(aligned_x as u32 + 16, 10).unwrap(), Color::BLACK);
+        assert_eq!(atlas.pixel(aligned_x as u32, 11).unwrap(), Color::BLACK);
+
+        // views at the bottom right corner don't reach past the parent
+        let corner = Rectangle { x: 64 - aligned_x, y: 63, width: aligned_x, height: 1 };
+        assert_eq!(atlas.region(corner).unwrap().pixel(0, 0).unwrap(), Color::BLACK);
+
+        let mut target = Buffer::allocate(16, 8, Format::RGBA8888).unwrap();
+        target.clear(None, Color::BLACK).unwrap();
+        let mut icon = atlas.region(rect).unwrap();
+        target.blit(&mut icon, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(0, 0).unwrap(), Color::WHITE);
+        assert_eq!(target.pixel(15, 7).unwrap(), Color::rgb(255, 0, 0));
+        drop(icon);
+
+        assert!(matches!(atlas.region(Rectangle { x: 1, ..rect }), Err(Error::NotAligned)));
+        assert!(matches!(atlas.region(Rectangle { width: 64, ..rect }), Err(Error::InvalidArgument)));
+        assert!(matches!(atlas.region(Rectangle { x: -aligned_x, ..rect }), Err(Error::InvalidArgument)));
+        assert!(matches!(atlas.region(Rectangle { height: 0, ..rect }), Err(Error::InvalidArgument)));
+    }
+
+    fn solid(width: u32, height: u32, color: Color) -> Image {
+        Image::new(width, height, vec![color; (width * height) as usize]).unwrap()
+    }
+
+    #[test]
+    fn atlas() {
+        let (_gpu, ctx) = context(64, 64);
+        let red = Color::rgb(255, 0, 0);
+        let mut atlas = AtlasBuilder::new(64, 32, Format::RGBA8888)
+            .padding(1)
+            .add("wide", solid(40, 8, red))
+            .add("tall", solid(8, 20, Color::rgb(0, 0, 255)))
+            .add_encoded("png", &solid(4, 4, Color::WHITE).encode(ImageFormat::Png).unwrap()).unwrap()
+            .add_raw("raw", PixelSlice::new(&[0x00, 0xf8, 0xe0, 0x07], 2, 1, Format::BGR565).unwrap()).unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(atlas.entries().count(), 4);
+        let tall = atlas.get("tall").unwrap();
+        assert_eq!((tall.width, tall.height), (8, 20));
+        // tallest first, then the rest goes next to it
+        assert_eq!((tall.x, tall.y), (0, 0));
+        let wide = atlas.get("wide").unwrap();
+        assert_eq!((wide.x, wide.y), (9, 0));
+        let handles: Vec<_> = atlas.entries().map(|(_, h)| h).collect();
+        for (i, a) in handles.iter().enumerate() {
+            for b in &handles[i + 1..] {
+                assert!(a.x + a.width <= b.x || b.x + b.width <= a.x || a.y + a.height <= b.y || b.y + b.height <= a.y);
+            }
+        }
+        let usage = atlas.usage();
+        assert_eq!(usage.used, 40 * 8 + 8 * 20 + 16 + 2);
+        assert_eq!(usage.used + usage.wasted + usage.free, 64 * 32);
+
+        let later = atlas.insert("later", &solid(10, 10, Color::rgb(0, 255, 0))).unwrap();
+        assert!(matches!(atlas.insert("later", &solid(1, 1, red)), Err(Error::AlreadyExists)));
+        assert!(matches!(atlas.insert("huge", &solid(65, 1, red)), Err(Error::OutOfResource)));
+        assert!(atlas.usage().free < usage.free);
+
+        let mut target = Buffer::allocate(16, 16, Format::RGBA8888).unwrap();
+        for (name, color) in [("later", Color::rgb(0, 255, 0)), ("wide", red), ("png", Color::WHITE)] {
+            target.clear(None, Color::BLACK).unwrap();
+            let handle = atlas.get(name).unwrap();
+            let mut matrix = Transform::default();
+            matrix.translate(2., 3.);
+            atlas.blit(&mut target, handle, &mut matrix, Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(target.pixel(2, 3).unwrap(), color, "{}", name);
+            assert_eq!(target.pixel(1, 3).unwrap(), Color::BLACK, "{}", name);
+            let (right, bottom) = ((2 + handle.width).min(16), (3 + handle.height).min(16));
+            assert_eq!(target.pixel(right - 1, bottom - 1).unwrap(), color, "{}", name);
+            if right < 16 {
+                assert_eq!(target.pixel(right, 3).unwrap(), Color::BLACK, "{}", name);
+            }
+        }
+        let raw = atlas.get("raw").unwrap();
+        assert_eq!(atlas.buffer().pixel(raw.x, raw.y).unwrap(), red);
+        assert_eq!(atlas.buffer().pixel(raw.x + 1, raw.y).unwrap(), Color::rgb(0, 255, 0));
+        assert_eq!(atlas.buffer().pixel(later.x, later.y).unwrap(), Color::rgb(0, 255, 0));
+
+        let mut aligned = AtlasBuilder::new(64, 16, Format::RGB888).aligned(true)
+            .add("a", solid(3, 3, red))
+            .add("b", solid(3, 3, red))
+            .build()
+            .unwrap();
+        let b = aligned.get("b").unwrap();
+        assert_eq!(b.x % 64, 0);
+        if aligned.buffer().stride().is_multiple_of(ADDRESS_ALIGNMENT) {
+            assert_eq!(aligned.region(b).unwrap().pixel(0, 0).unwrap(), red);
+        }
+        let mut later = Atlas::new(64, 16, Format::RGB888, AtlasOptions { padding: 1, aligned: true }).unwrap();
+        later.insert("a", &solid(3, 3, red)).unwrap();
+        // the first image fills a whole aligned step, its padding pushes the next row down
+        let b = later.insert("b", &solid(3, 3, red)).unwrap();
+        assert_eq!((b.x, b.y), (0, 4));
+    }
+
+    #[test]
+    fn tint_icon() {
+        let (_gpu, ctx) = context(64, 64);
+        let Some(mut icon) = allocate(4, 1, Format::A8) else { return };
+        icon.upload(&[255, 0, 128, 255], 4).unwrap();
+        assert_eq!(icon.image_mode(), ImageMode::Normal);
+        let mut target = Buffer::allocate(4, 1, Format::RGBA8888).unwrap();
+        for color in [Color::rgb(255, 0, 0), Color::rgb(30, 200, 90)] {
+            target.clear(None, Color::BLACK).unwrap();
+            target.tint_blit(&mut icon, &mut Transform::default(), color, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(target.pixel(0, 0).unwrap(), color);
+            assert_eq!(target.pixel(1, 0).unwrap(), Color::BLACK);
+            let half = target.pixel(2, 0).unwrap();
+            for (a, b) in [(half.r, color.r / 2), (half.g, color.g / 2), (half.b, color.b / 2)] {
+                assert!(a.abs_diff(b) <= 2, "{:?}", half);
+            }
+            assert_eq!(target.pixel(3, 0).unwrap(), color);
+        }
+        // the icon is left as it was
+        assert_eq!(icon.image_mode(), ImageMode::Normal);
+        let mut opaque = Buffer::allocate(4, 1, Format::RGB565).unwrap();
+        assert!(target.tint_blit(&mut opaque, &mut Transform::default(), Color::WHITE, Filter::Pointer).is_err());
+
+        icon.set_image_mode(ImageMode::Multiply);
+        assert_eq!(icon.image_mode(), ImageMode::Multiply);
+        assert_eq!(icon.transparency(), Transparency::Opaque);
+        icon.set_transparency(Transparency::Transparent);
+        assert_eq!(icon.transparency(), Transparency::Transparent);
+    }
+
+    #[test]
+    fn global_alpha() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut source = Buffer::allocate(2, 1, Format::RGBA8888).unwrap();
+        source.upload(&[255, 255, 255, 255, 255, 255, 255, 128], 8).unwrap();
+        let mut target = Buffer::allocate(2, 1, Format::RGBA8888).unwrap();
+        let mut blit = |target: &mut Buffer, opacity| {
+            target.clear(None, Color::BLACK).unwrap();
+            let result = target.blit_with_opacity(
+                &ctx, &mut source, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer, opacity
+            );
+            ctx.finish().unwrap();
+            result.map(|_| (target.pixel(0, 0).unwrap().r, target.pixel(1, 0).unwrap().r))
+        };
+        let Ok(half) = blit(&mut target, 128) else { return };
+        assert!(half.0.abs_diff(128) <= 2 && half.1.abs_diff(64) <= 2, "{:?}", half);
+        // back to the pixels' own alpha afterwards
+        let full = blit(&mut target, 255).unwrap();
+        assert!(full.0 == 255 && full.1.abs_diff(128) <= 2, "{:?}", full);
+        {
+            // faded on top of the outer alpha, which is back afterwards
+            let _outer = ctx.source_global_alpha(GlobalAlpha::Global(128)).unwrap();
+            let quarter = blit(&mut target, 128).unwrap();
+            assert!(quarter.0.abs_diff(64) <= 2 && quarter.1.abs_diff(64) <= 2, "{:?}", quarter);
+            assert_eq!(ctx.render_state().source_alpha, GlobalAlpha::Global(128));
+        }
+        assert_eq!(ctx.render_state().source_alpha, GlobalAlpha::Normal);
+
+        {
+            let _global = ctx.source_global_alpha(GlobalAlpha::Global(0)).unwrap();
+            target.clear(None, Color::BLACK).unwrap();
+            target.blit(&mut source, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(target.pixel(0, 0).unwrap(), Color::BLACK);
+        }
+        target.blit(&mut source, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(0, 0).unwrap(), Color::WHITE);
+
+        {
+            let _dest = ctx.dest_global_alpha(GlobalAlpha::Scaled(0)).unwrap();
+            target.clear(None, Color::BLACK).unwrap();
+            target.blit(&mut source, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert!(target.pixel(1, 0).unwrap().a.abs_diff(128) <= 2, "{:?}", target.pixel(1, 0).unwrap());
+        }
+    }
+
+    #[test]
+    fn color_key_ranges() {
+        let magenta = Color::rgb(255, 0, 255);
+        let key = ColorKey::new()
+            .color(magenta, 0)
+            .range(Color::rgb(200, 0, 200), Color::rgb(255, 50, 255), 128)
+            .range(Color::rgb(0, 0, 0), Color::rgb(255, 255, 255), 255);
+        key.validate().unwrap();
+        // the first matching range wins where they overlap
+        assert_eq!(key.alpha_for(magenta), Some(0));
+        assert_eq!(key.alpha_for(Color::rgb(220, 10, 230)), Some(128));
+        assert_eq!(key.alpha_for(Color::rgb(10, 10, 10)), Some(255));
+        let key = key.enable(0, false);
+        assert_eq!(key.alpha_for(magenta), Some(128));
+        assert_eq!(key.ranges().len(), 3);
+        assert_eq!(ColorKey::new().alpha_for(magenta), None);
+
+        assert!(ColorKey::new().range(Color::rgb(10, 0, 0), Color::rgb(9, 0, 0), 0).validate().is_err());
+        assert!(ColorKey::new().range(Color::rgb(0, 0, 5), Color::rgb(9, 9, 4), 0).validate().is_err());
+        let five = (0..5).fold(ColorKey::new(), |key, i| key.color(Color::rgb(i, i, i), 0));
+        assert!(five.validate().is_err());
+        assert!(five.clone().enable(4, false).validate().is_err());
+    }
+
+    #[test]
+    fn color_key_blit() {
+        let (_gpu, ctx) = context(64, 64);
+        let magenta = Color::rgb(255, 0, 255);
+        let Some(mut bitmap) = allocate(3, 1, Format::RGB888) else { return };
+        bitmap.upload(&[255, 0, 255, 250, 5, 250, 0, 255, 0], 9).unwrap();
+        let mut target = Buffer::allocate(3, 1, Format::RGBA8888).unwrap();
+        let key = ColorKey::new()
+            .color(magenta, 0)
+            .range(Color::rgb(240, 0, 240), Color::rgb(255, 10, 255), 0);
+        {
+            let Ok(_key) = ctx.color_key(&key) else { return };
+            target.clear(None, Color::BLACK).unwrap();
+            target.blit(&mut bitmap, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(target.pixel(0, 0).unwrap(), Color::BLACK);
+            assert_eq!(target.pixel(1, 0).unwrap(), Color::BLACK);
+            assert_eq!(target.pixel(2, 0).unwrap(), Color::rgb(0, 255, 0));
+        }
+        target.blit(&mut bitmap, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(0, 0).unwrap(), magenta);
+        assert!(ctx.color_key(&ColorKey::new().range(Color::WHITE, Color::BLACK, 0)).is_err());
+    }
+
+    fn close(a: Color, b: Color) -> bool {
+        [(a.r, b.r), (a.g, b.g), (a.b, b.b), (a.a, b.a)].iter().all(|&(x, y)| x.abs_diff(y) <= 2)
+    }
+
+    #[test]
+    fn color_matrix_presets() {
+        let orange = Color::rgba(230, 120, 40, 200);
+        assert_eq!(ColorMatrix::IDENTITY.apply(orange), orange);
+        assert_eq!(ColorMatrix::grayscale().apply(Color::WHITE), Color::WHITE);
+        let gray = ColorMatrix::grayscale().apply(orange);
+        assert!(gray.r == gray.g && gray.g == gray.b && gray.a == 200);
+        assert_eq!(ColorMatrix::invert().apply(orange), Color::rgba(25, 135, 215, 200));
+        assert_eq!((ColorMatrix::invert() * ColorMatrix::invert()).apply(orange), orange);
+        assert!(close(ColorMatrix::hue_rotate(360.).apply(orange), orange));
+        assert!(close(ColorMatrix::hue_rotate(120.).apply(Color::rgb(128, 128, 128)), Color::rgb(128, 128, 128)));
+        assert_eq!(ColorMatrix::contrast(0.).apply(orange), Color::rgba(128, 128, 128, 200));
+        assert_eq!(ColorMatrix::brightness(0.5).apply(Color::WHITE), Color::rgb(128, 128, 128));
+        assert_eq!(ColorMatrix::sepia().apply(Color::WHITE), Color::rgb(255, 255, 239));
+        assert_eq!(ColorMatrix::invert().apply_channels(orange, PixelChannels::NONE), orange);
+
+        // composing then applying matches applying one after the other
+        let a = ColorMatrix::sepia() * ColorMatrix::contrast(1.2);
+        let b = ColorMatrix::hue_rotate(45.) * ColorMatrix::brightness(0.8);
+        let composed = (a * b).apply(orange);
+        assert!(close(composed, a.apply(b.apply(orange))), "{composed:?}");
+    }
+
+    #[test]
+    fn color_matrix_blit() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut source = Buffer::from_image(&solid(2, 2, Color::rgb(200, 50, 10)), Format::RGBA8888).unwrap();
+        let mut target = Buffer::allocate(2, 2, Format::RGBA8888).unwrap();
+        let matrix = ColorMatrix::invert() * ColorMatrix::grayscale();
+        {
+            let Ok(_matrix) = ctx.pixel_matrix(&matrix, PixelChannels::RGB) else { return };
+            target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert!(close(target.pixel(1, 1).unwrap(), matrix.apply(Color::rgb(200, 50, 10))));
+        }
+        target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(1, 1).unwrap(), Color::rgb(200, 50, 10));
+    }
+
+    #[test]
+    fn gaussian_weights() {
+        for sigma in [0.3, 0.8, 2.] {
+            let w = GaussianWeights::from_sigma(sigma);
+            assert!((w.w0 + 4. * w.w1 + 4. * w.w2 - 1.).abs() < 1e-6);
+            assert!(w.w0 > w.w1 && w.w1 > w.w2);
+        }
+        assert_eq!(GaussianWeights::from_sigma(0.), GaussianWeights { w0: 1., w1: 0., w2: 0. });
+        assert!(GaussianWeights::from_sigma(0.3).variance() < GaussianWeights::from_sigma(0.8).variance());
+        assert_eq!(GaussianWeights::default().variance(), 0.5);
+    }
+
+    #[test]
+    fn gaussian_blur() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut dot = Buffer::allocate(3, 3, Format::RGBA8888).unwrap();
+        dot.clear(None, Color::BLACK).unwrap();
+        dot.clear(Some(&mut Rectangle { x: 1, y: 1, width: 1, height: 1 }), Color::WHITE).unwrap();
+        let mut target = Buffer::allocate(3, 3, Format::RGBA8888).unwrap();
+        let filter = Filter::Gaussian(GaussianWeights::default());
+        if target.blit(&mut dot, &mut Transform::default(), Blend::None, Color::WHITE, filter).is_err() {
+            return;
+        }
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(1, 1).unwrap(), Color::rgb(64, 64, 64));
+        assert_eq!(target.pixel(1, 0).unwrap(), Color::rgb(32, 32, 32));
+        assert_eq!(target.pixel(0, 0).unwrap(), Color::rgb(16, 16, 16));
+
+        let mut panel = Buffer::allocate(32, 32, Format::RGBA8888).unwrap();
+        panel.clear(None, Color::BLACK).unwrap();
+        panel.clear(Some(&mut Rectangle { x: 0, y: 0, width: 16, height: 32 }), Color::WHITE).unwrap();
+        blur(&mut panel, 4.).unwrap();
+        ctx.finish().unwrap();
+        let row: Vec<u8> = (0..32).map(|x| panel.pixel(x, 16).unwrap().r).collect();
+        assert!(row.windows(2).all(|w| w[0] >= w[1]), "{row:?}");
+        assert!(row[0] > 240 && row[31] < 16, "{row:?}");
+        assert!(row[12] < 250 && row[19] > 5, "{row:?}");
+        assert!(blur(&mut panel, 0.).is_ok());
+    }
+
+    #[test]
+    fn color_transform() {
+        let teal = Color::rgba(0, 128, 128, 200);
+        assert_eq!(ColorTransform::IDENTITY.apply(teal), teal);
+        assert_eq!(ColorTransform::tint(Color::rgb(255, 0, 255)).apply(Color::WHITE), Color::rgb(255, 0, 255));
+        assert_eq!(ColorTransform::alpha_multiply(0.5).apply(teal), Color::rgba(0, 128, 128, 100));
+        let dark = ColorTransform { scale: [0.5; 4], bias: [0., 0., 0.1, 0.5] };
+        assert_eq!(ColorTransform::lerp(&ColorTransform::IDENTITY, &dark, 0.), ColorTransform::IDENTITY);
+        assert_eq!(ColorTransform::lerp(&ColorTransform::IDENTITY, &dark, 1.), dark);
+        assert_eq!(ColorTransform::lerp(&ColorTransform::IDENTITY, &dark, 0.5).scale, [0.75; 4]);
+
+        let (_gpu, ctx) = context(64, 64);
+        let mut source = Buffer::from_image(&solid(2, 2, teal), Format::RGBA8888).unwrap();
+        let mut target = Buffer::allocate(2, 2, Format::RGBA8888).unwrap();
+        {
+            let Ok(mut transform) = ctx.color_transform(&dark) else { return };
+            target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert!(close(target.pixel(0, 0).unwrap(), dark.apply(teal)));
+            transform.set(&ColorTransform::IDENTITY).unwrap();
+            target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            assert_eq!(target.pixel(0, 0).unwrap(), teal);
+        }
+        target.clear(None, Color::BLACK).unwrap();
+        target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+        ctx.finish().unwrap();
+        assert_eq!(target.pixel(1, 1).unwrap(), teal);
+    }
+
+    #[test]
+    fn render_settings() {
+        let (_gpu, ctx) = context(64, 64);
+        assert!(!ctx.dither());
+        assert_eq!(ctx.gamma(), Gamma::NoConversion);
+        if ctx.set_dither(true).is_ok() {
+            assert!(ctx.dither());
+            ctx.set_dither(false).unwrap();
+            assert!(!ctx.dither());
+        }
+        if ctx.set_gamma(Gamma::NonLinear).is_ok() {
+            assert_eq!(ctx.gamma(), Gamma::NonLinear);
+        }
+        if ctx.set_mirror(Mirror::BottomTop).is_ok() {
+            assert_eq!(ctx.mirror(), Mirror::BottomTop);
+        }
+        let premultiply = Premultiply { source: true, dest: false };
+        if ctx.set_premultiply(premultiply).is_ok() {
+            assert_eq!(ctx.premultiply(), premultiply);
+        }
+    }
+
+    #[test]
+    fn with_state() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut source = Buffer::from_image(&solid(1, 1, Color::rgb(255, 0, 255)), Format::RGBA8888).unwrap();
+        let mut target = Buffer::allocate(1, 1, Format::RGBA8888).unwrap();
+        let mut blit = |ctx: &Context| {
+            target.clear(None, Color::BLACK).unwrap();
+            target.blit(&mut source, &mut Transform::default(), Blend::SourceOver, Color::WHITE, Filter::Pointer).unwrap();
+            ctx.finish().unwrap();
+            target.pixel(0, 0).unwrap()
+        };
+        let keyed = RenderState {
+            color_key: ColorKey::new().color(Color::rgb(255, 0, 255), 0),
+            dither: true,
+            ..Default::default()
+        };
+        let halved = RenderState { color_transform: Some(ColorTransform::tint(Color::rgb(128, 128, 128))), ..keyed.clone() };
+        let Ok(inside) = ctx.with_state(&keyed, |ctx| {
+            assert!(ctx.dither());
+            let keyed = blit(ctx);
+            // nested states only change what differs and come back to the outer one
+            let halved = ctx.with_state(&halved, |ctx| ctx.render_state().color_transform).unwrap();
+            assert!(halved.is_some());
+            assert!(ctx.render_state().color_transform.is_none());
+            {
+                let _alpha = ctx.source_global_alpha(GlobalAlpha::Global(0x80)).unwrap();
+                assert_eq!(ctx.render_state().source_alpha, GlobalAlpha::Global(0x80));
+            }
+            assert_eq!(ctx.render_state().source_alpha, GlobalAlpha::Normal);
+            (keyed, ctx.render_state().color_key)
+        }) else { return };
+        assert_eq!(inside, (Color::BLACK, keyed.color_key.clone()));
+        assert!(!ctx.dither());
+        assert_eq!(ctx.render_state().color_key, ColorKey::new());
+        assert_eq!(blit(&ctx), Color::rgb(255, 0, 255));
+
+        // a panicking draw doesn't leak its settings into the next ones
+        let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = ctx.with_state(&keyed, |_| panic!("draw failed"));
+        }));
+        assert!(caught.is_err());
+        assert!(!ctx.dither());
+        assert_eq!(ctx.render_state().color_key, ColorKey::new());
+    }
+
+    #[test]
+    fn tess_window() {
+        let (_gpu, mut ctx) = context(32, 32);
+        assert_eq!(ctx.tess_window(), (32, 32));
+        assert!(matches!(ctx.set_command_buffer(0x1000_0010, 0x8000), Err(Error::NotAligned)));
+        assert!(matches!(ctx.set_tess_buffer(0x1000_0020, 0x8000), Err(Error::NotAligned)));
+
+        let mut data = PathData::<f32>::default();
+        data.move_to(0., 0.).line_to(48., 0.).line_to(48., 20.).line_to(0., 20.).close();
+        let mut path = data.fill(Quality::High);
+        let mut target = Buffer::allocate(64, 64, Format::RGBA8888).unwrap();
+        let mut draw = |target: &mut Buffer, x: f32| target.draw(
+            &mut path, Fill::NonZero, Transform::default().translate(x, 0.), Blend::None, Color::WHITE
+        );
+        // split by the driver unless asked to fail
+        draw(&mut target, 0.).unwrap();
+        ctx.set_tess_window_check(true);
+        assert!(matches!(draw(&mut target, 0.), Err(Error::TessWindowExceeded)));
+        // only the part inside the target counts
+        draw(&mut target, 40.).unwrap();
+        // blits aren't tessellated
+        let mut source = Buffer::allocate(48, 20, Format::RGBA8888).unwrap();
+        target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+
+        let _ = ctx.set_dither(true);
+        assert!(!ctx.grow_tess_window(16, 32).unwrap());
+        assert!(ctx.grow_tess_window(64, 16).unwrap());
+        assert_eq!(ctx.tess_window(), (64, 32));
+        assert!(!ctx.dither());
+        draw(&mut target, 0.).unwrap();
+        ctx.set_tess_window_check(false);
+    }
+
+    #[test]
+    fn memory_budget() {
+        struct Glyphs(Vec<Buffer<'static>>);
+        impl MemoryCache for Glyphs {
+            fn evict(&mut self, bytes: u64) -> u64 {
+                let mut freed = 0;
+                while freed < bytes {
+                    let Some(glyph) = self.0.pop() else { break };
+                    freed += glyph.allocated_bytes();
+                }
+                freed
+            }
+        }
+
+        let (_gpu, ctx) = context(64, 64);
+        let before = ctx.memory_stats().unwrap();
+        assert!(before.available > 0);
+        let buffer = Buffer::allocate(32, 32, Format::RGBA8888).unwrap();
+        assert!(buffer.allocated_bytes() >= 32 * 32 * 4);
+        // reserved and tracked with the same padded rows
+        assert_eq!(memory::estimate(3, 2, Format::RGBA8888), 2 * ADDRESS_ALIGNMENT as u64);
+        assert_eq!(buffer.allocated_bytes(), memory::estimate(32, 32, Format::RGBA8888));
+        let stats = ctx.memory_stats().unwrap();
+        assert_eq!(stats.allocated, before.allocated + buffer.allocated_bytes());
+        assert_eq!(stats.buffers, before.buffers + 1);
+        drop(buffer);
+        assert_eq!(ctx.memory_stats().unwrap().allocated, before.allocated);
+
+        let glyphs = Rc::new(RefCell::new(Glyphs(Vec::new())));
+        ctx.register_cache(&glyphs);
+        for _ in 0..2 {
+            let glyph = Buffer::allocate(32, 32, Format::RGBA8888).unwrap();
+            glyphs.borrow_mut().0.push(glyph);
+        }
+        ctx.set_memory_budget(Some(before.allocated + 10_000));
+        let panel = Buffer::allocate(48, 32, Format::RGBA8888).unwrap();
+        assert!(glyphs.borrow().0.is_empty());
+        assert!(matches!(Buffer::allocate(64, 64, Format::RGBA8888), Err(Error::OutOfMemory)));
+        ctx.set_memory_budget(None);
+        assert!(ctx.memory_stats().unwrap().peak >= before.allocated + panel.allocated_bytes());
+
+        // the driver runs out while the evicted buffer is still read by the GPU
+        let rows = ctx.memory_stats().unwrap().available / 4096 * 2 / 3;
+        let mut glyph = Buffer::allocate(1024, rows, Format::RGBA8888).unwrap();
+        glyph.clear(None, Color::BLACK).unwrap();
+        glyphs.borrow_mut().0.push(glyph);
+        let large = Buffer::allocate(1024, rows, Format::RGBA8888).unwrap();
+        assert!(glyphs.borrow().0.is_empty());
+        assert_eq!(ctx.in_flight(), 0);
+        drop(large);
+
+        // same within the budget
+        let mut glyph = Buffer::allocate(32, 32, Format::RGBA8888).unwrap();
+        glyph.clear(None, Color::BLACK).unwrap();
+        glyphs.borrow_mut().0.push(glyph);
+        ctx.set_memory_budget(Some(ctx.memory_stats().unwrap().allocated));
+        let replacement = Buffer::allocate(32, 32, Format::RGBA8888);
+        ctx.set_memory_budget(None);
+        replacement.unwrap();
+        assert_eq!(ctx.in_flight(), 0);
+    }
+
+    #[test]
+    fn buffer_pool() {
+        let (_gpu, ctx) = context(64, 64);
+        let pool = BufferPool::new(3 * 32 * 32 * 4);
+        let address = {
+            let mut lease = pool.acquire(32, 32, Format::RGBA8888).unwrap();
+            lease.set_image_mode(ImageMode::Multiply);
+            lease.set_yuv_standard(YuvStandard::Bt709);
+            lease.set_uv_swizzle(UvSwizzle::Vu);
+            lease.set_index_endian(IndexEndian::Big);
+            lease.buffer.address
+        };
+        assert_eq!(pool.idle_count(), 1);
+        let buffers = ctx.memory_stats().unwrap().buffers;
+        {
+            let lease = pool.acquire(32, 32, Format::RGBA8888).unwrap();
+            assert_eq!(lease.buffer.address, address);
+            assert_eq!(lease.image_mode(), Buffer::allocate(1, 1, Format::RGBA8888).unwrap().image_mode());
+            assert_eq!(lease.yuv_standard(), YuvStandard::default());
+            assert_eq!(lease.uv_swizzle(), UvSwizzle::Uv);
+            assert_eq!(lease.index_endian(), IndexEndian::default());
+            assert_eq!(pool.idle_count(), 0);
+            let other = pool.acquire(32, 32, Format::BGR565).unwrap();
+            assert_ne!(other.buffer.address, address);
+        }
+        assert_eq!(pool.idle_count(), 2);
+        assert_eq!(ctx.memory_stats().unwrap().buffers, buffers + 1);
+
+        let detached = pool.acquire(16, 16, Format::A8).unwrap().detach();
+        assert_eq!(detached.width(), 16);
+        assert_eq!(pool.idle_count(), 2);
+
+        // over the high-water mark the least recently returned buffers go first
+        let leases: Vec<_> = (0..3).map(|_| pool.acquire(32, 32, Format::RGBA8888).unwrap()).collect();
+        drop(leases);
+        assert!(pool.idle_bytes() <= pool.high_water());
+        assert_eq!(pool.idle_count(), 3);
+        pool.set_high_water(32 * 32 * 4);
+        assert_eq!(pool.idle_count(), 1);
+        pool.clear();
+        assert_eq!((pool.idle_count(), pool.idle_bytes()), (0, 0));
+
+        // trimmed over the budget, also while some of its buffers are leased
+        let pool = std::rc::Rc::new(BufferPool::new(4 * 32 * 32 * 4));
+        ctx.register_pool(&pool);
+        let lease = pool.acquire(32, 32, Format::RGBA8888).unwrap();
+        drop([pool.acquire(32, 32, Format::RGBA8888).unwrap(), pool.acquire(32, 32, Format::RGBA8888).unwrap()]);
+        assert_eq!(pool.idle_count(), 2);
+        ctx.set_memory_budget(Some(ctx.memory_stats().unwrap().allocated));
+        let extra = Buffer::allocate(32, 32, Format::RGBA8888);
+        ctx.set_memory_budget(None);
+        extra.unwrap();
+        assert_eq!(pool.idle_count(), 1);
+        drop(lease);
+        assert_eq!(pool.idle_count(), 2);
+    }
+
+    #[test]
+    fn fences() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::{Poll, Wake, Waker};
+        use std::time::Duration;
+
+        struct Unpark(std::thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let (_gpu, ctx) = context(64, 64);
+        let mut target = Buffer::allocate(8, 8, Format::RGBA8888).unwrap();
+        target.clear(None, Color::WHITE).unwrap();
+        let first = ctx.flush().unwrap();
+        target.clear(None, Color::BLACK).unwrap();
+        let second = ctx.flush().unwrap();
+        second.wait_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(second.check(), Some(Ok(()))));
+        first.wait().unwrap();
+        assert_eq!(target.pixel(0, 0).unwrap(), Color::BLACK);
+
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut fence = std::pin::pin!(ctx.flush().unwrap());
+        let result = loop {
+            match fence.as_mut().poll(&mut std::task::Context::from_waker(&waker)) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => std::thread::park_timeout(Duration::from_millis(100))
+            }
+        };
+        result.unwrap();
+    }
+
+    #[test]
+    fn in_flight_releases() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut target = Buffer::allocate(8, 8, Format::RGBA8888).unwrap();
+        let buffers = ctx.memory_stats().unwrap().buffers;
+        {
+            let mut source = Buffer::allocate(4, 4, Format::RGBA8888).unwrap();
+            source.clear(None, Color::rgb(255, 0, 0)).unwrap();
+            target.blit(&mut source, &mut Transform::default(), Blend::None, Color::WHITE, Filter::Pointer).unwrap();
+            let mut data = PathData::<f32>::default();
+            data.move_to(4., 4.).line_to(8., 4.).line_to(8., 8.).line_to(4., 8.).close();
+            let mut path = data.fill(Quality::High);
+            target.draw(&mut path, Fill::NonZero, &mut Transform::default(), Blend::None, Color::rgb(0, 0, 255)).unwrap();
+        }
+        assert_eq!(ctx.in_flight(), 2);
+        assert_eq!(ctx.memory_stats().unwrap().buffers, buffers + 1);
+        ctx.finish().unwrap();
+        assert_eq!(ctx.in_flight(), 0);
+        assert_eq!(ctx.memory_stats().unwrap().buffers, buffers);
+        assert_eq!(target.pixel(0, 0).unwrap(), Color::rgb(255, 0, 0));
+
+        drop(Buffer::allocate(4, 4, Format::RGBA8888).unwrap());
+        assert_eq!(ctx.in_flight(), 0);
+
+        Buffer::allocate(4, 4, Format::RGBA8888).unwrap().clear(None, Color::rgb(255, 0, 0)).unwrap();
+        assert_eq!(ctx.in_flight(), 1);
+        ctx.flush().unwrap().wait().unwrap();
+        drop(Buffer::allocate(4, 4, Format::RGBA8888).unwrap());
+        assert_eq!(ctx.in_flight(), 0);
+
+        let mut memory = vec![0u8; 8 * 64];
+        Buffer::map_user_memory(&mut memory, 8, 8, 64, Format::RGBA8888).unwrap()
+            .clear(None, Color::rgb(1, 2, 3)).unwrap();
+        assert_eq!(ctx.in_flight(), 0);
+        assert_eq!(&memory[..4], &[1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn path_ownership() {
+        // no driver calls, so that miri checks the pointers on its own
+        fn encoded(path: &mut Path<i16>) -> Vec<i16> {
+            let raw = path.raw_mut();
+            assert_eq!(raw.path_length as usize % 2, 0);
+            unsafe { core::slice::from_raw_parts(raw.path as *const i16, raw.path_length as usize / 2) }.to_vec()
+        }
+
+        let mut data = PathData::<i16>::default();
+        data.move_to(0, 0).line_to(10, 0).line_to(10, 5).close();
+        let expected = data.encoded().to_vec();
+        let mut path = data.fill(Quality::Medium);
+        let mut clone = path.clone();
+        assert_ne!(path.raw_mut().path, clone.raw_mut().path);
+        assert_eq!(clone.bounding_box(), [0., 0., 10., 5.]);
+        assert_eq!(clone.raw_mut().quality, path.raw_mut().quality);
+        assert_eq!(clone.raw_mut().path_type, path.raw_mut().path_type);
+        drop(path);
+        assert_eq!(encoded(&mut clone), expected);
+
+        let mut moved = vec![clone];
+        assert_eq!(encoded(&mut moved[0]), expected);
+        let mut boxed = Box::new(moved.remove(0));
+        assert_eq!(encoded(&mut boxed), expected);
+        assert_eq!(boxed.data().encoded(), &expected[..]);
+        assert!(matches!(boxed.format(), DataFormat::I16));
+
+        let mut empty = PathData::<f32>::default().fill(Quality::High).clone();
+        assert_eq!(empty.raw_mut().path_length, 0);
+    }
+
+    #[test]
+    fn edit_path() {
+        let (_gpu, ctx) = context(64, 64);
+        let mut data = PathData::<f32>::default();
+        data.move_to(0., 0.).line_to(8., 0.).line_to(8., 8.).close();
+        let mut path = data.fill(Quality::High);
+        path.upload().unwrap();
+        assert!(path.is_uploaded());
+        let mut target = Buffer::allocate(16, 16, Format::RGBA8888).unwrap();
+        target.draw(&mut path, Fill::NonZero, &mut Transform::default(), Blend::None, Color::WHITE).unwrap();
+
+        let drawn = path.raw_mut().path;
+        {
+            let mut edit = path.edit();
+            edit.clear().move_to(2., 1.).line_to(12., 1.).line_to(12., 6.).line_to(2., 6.).close();
+        }
+        assert_eq!(ctx.in_flight(), 1);
+        assert!(!path.is_uploaded());
+        assert_ne!(path.raw_mut().path, drawn);
+        assert_eq!(path.bounding_box(), [2., 1., 12., 6.]);
+        assert_eq!(path.raw_mut().path_length as usize, path.data().encoded().len() * 4);
+        assert_eq!(path.raw_mut().path_changed, 1);
+        ctx.finish().unwrap();
+        assert_eq!(ctx.in_flight(), 0);
+
+        path.raw_mut().path_changed = 0;
+        path.edit().line_to(14., 14.);
+        assert_eq!(ctx.in_flight(), 0);
+        assert_eq!(path.raw_mut().path_changed, 1);
+        assert_eq!(path.bounding_box(), [2., 1., 14., 14.]);
+
+        path.set_auto_upload(true);
+        assert_eq!(path.raw_mut().uploaded.property & 1, 1);
+        path.upload().unwrap();
+        path.edit();
+        assert!(!path.is_uploaded());
+        assert_eq!(path.raw_mut().uploaded.property & 1, 1);
+    }
+
+    #[test]
+    fn quantize_path() {
+        // coordinates of a move, a line, a cubic and a close, mapped back by the compensation
+        fn decode<T: OpCodeFormat>(data: &PathData<T>, compensation: &Transform) -> Vec<f32> {
+            let m = &compensation.m;
+            let axes = [0, 1, 0, 1, 0, 1, 0, 1, 0, 1];
+            let values = data.encoded().iter().enumerate().filter(|(i, _)| ![0, 3, 6, 13].contains(i));
+            values.zip(axes).map(|((_, v), axis)| v.to_f64() as f32 * m[axis][axis] + m[axis][2]).collect()
+        }
+
+        let mut data = PathData::<f32>::default();
+        data.move_to(1000.25, -200.).line_to(1400.5, -200.).curve_to(1400.5, 100., 1000.25, 100., 1000.25, -200.).close();
+        let (quantized, compensation) = data.quantize::<i16>().unwrap();
+        assert_eq!(quantized.encoded().len(), data.encoded().len());
+        assert_eq!(quantized.encoded()[13], 1);
+        let unit = compensation.m[0][0];
+        assert_eq!(unit, 1. / 128.);
+        let original = decode(&data, &Transform::default());
+        for (value, expected) in decode(&quantized, &compensation).into_iter().zip(original) {
+            assert!((value - expected).abs() <= unit / 2., "{value} {expected}");
+        }
+        assert!(quantized.encoded().iter().any(|&q| q.unsigned_abs() > 8192));
+        assert!(matches!(data.quantize::<i8>(), Err(Error::PrecisionLoss)));
+
+        let mut small = PathData::<f32>::default();
+        small.move_to(0., 0.).line_to(0.75, 0.5).curve_to(0., 0.5, 0.75, 0., 0.25, 0.25).close();
+        let (quantized, compensation) = small.quantize::<i8>().unwrap();
+        assert_eq!(compensation.m[0][0], 1. / 256.);
+        assert_eq!(decode(&quantized, &compensation), decode(&small, &Transform::default()));
+        let double = Transform { m: [[2., 0., 0.], [0., 2., 0.], [0., 0., 1.]] };
+        let (x, y) = (quantized.encoded()[4] as f32, quantized.encoded()[5] as f32);
+        let m = (double * compensation).m;
+        assert_eq!((m[0][0] * x + m[0][2], m[1][1] * y + m[1][2]), (1.5, 1.));
+
+        assert_eq!(data.convert::<f32>().unwrap().encoded(), data.encoded());
+        assert!(matches!(data.convert::<i16>(), Err(Error::PrecisionLoss)));
+        let mut whole = PathData::<i32>::default();
+        whole.move_to(100, 100).line_to(300, -5).close();
+        assert_eq!(whole.convert::<i16>().unwrap().encoded(), &[2, 100, 100, 4, 300, -5, 1]);
+        assert!(matches!(whole.convert::<i8>(), Err(Error::Overflow)));
+        assert!(matches!(whole.convert::<f32>().unwrap().convert::<i8>(), Err(Error::Overflow)));
+    }
+
+    #[test]
+    fn path_bounding_box() {
+        let mut data = PathData::<f32>::default();
+        data.move_to(0., 0.).line_to(4., 0.).line_to(4., 4.).close();
+        data.move_to(10., 10.).quad_to(20., 30., 12., 12.).append(Opcode::LineRel { dx: -15., dy: 0. });
+        assert_eq!(data.bounding_box(), [-3., 0., 20., 30.]);
+        let mut path = data.fill(Quality::High);
+        assert_eq!(path.bounding_box(), [-3., 0., 20., 30.]);
+
+        // a second subpath and a cubic control point beyond the first one
+        path.edit().clear().move_to(5., 5.).line_to(6., 6.).close()
+            .move_to(-8., 2.).curve_to(-8., -9., 0., 0., 1., 2.).close();
+        assert_eq!(path.bounding_box(), [-8., -9., 6., 6.]);
+
+        // arcs count with the ellipse they may follow
+        path.edit().clear().move_to(0., 0.).append(Opcode::SCWArc { rh: 5., rv: 5., rot: 0., x: 10., y: 0. });
+        let [x0, y0, x1, y1] = path.bounding_box();
+        assert!(x0 <= 0. && y0 <= -5. && x1 >= 10. && y1 >= 5., "{:?}", path.bounding_box());
+
+        let mut explicit = PathData::<i16>::default();
+        explicit.move_to(1, 1).line_to(2, 2).set_bbox(0, 0, 64, 64);
+        assert_eq!(explicit.bounding_box(), [0., 0., 64., 64.]);
+    }
+
+    #[test]
+    fn path_clone_uploaded() {
+        // runs under miri too, with the fake driver of `miri.rs`
+        let (_gpu, _ctx) = context(64, 64);
+        let mut data = PathData::<i16>::default();
+        data.move_to(0, 0).line_to(10, 0).line_to(10, 5).close();
+        let mut path = data.fill(Quality::High);
+        path.set_auto_upload(true);
+        path.upload().unwrap();
+        path.raw_mut().path_changed = 0;
+        let mut clone = path.clone();
+        assert!(clone.is_uploaded());
+        assert_ne!(clone.raw_mut().uploaded.address, path.raw_mut().uploaded.address);
+        assert_eq!(clone.raw_mut().uploaded.property & 1, 1);
+        assert_eq!(clone.raw_mut().path_changed, 0);
+        assert_ne!(clone.raw_mut().path, path.raw_mut().path);
+        drop(path);
+        assert_eq!(clone.data().encoded(), &[2, 0, 0, 4, 10, 0, 4, 10, 5, 1]);
+    }
+
+    #[test]
+    fn path_forgotten_edit() {
+        // runs under miri too, a draw reading the data freed by the edit is reported
+        let (_gpu, ctx) = context(64, 64);
+        let mut data = PathData::<f32>::default();
+        data.move_to(0., 0.).line_to(8., 0.).line_to(8., 8.).close();
+        let mut path = data.fill(Quality::High);
+        let mut target = Buffer::unbacked(16, 16, Format::RGBA8888);
+        target.draw(&mut path, Fill::NonZero, &mut Transform::default(), Blend::None, Color::WHITE).unwrap();
+
+        // the data drawn above is kept until the submission completes
+        std::mem::forget(path.edit());
+        ctx.finish().unwrap();
+        target.draw(&mut path, Fill::NonZero, &mut Transform::default(), Blend::None, Color::WHITE).unwrap();
+
+        let mut edit = path.edit();
+        for i in 0..64 {
+            edit.line_to(i as f32, 12.);
+        }
+        std::mem::forget(edit);
+        assert_eq!(path.bounding_box(), [0., 0., 63., 12.]);
+        target.draw(&mut path, Fill::NonZero, &mut Transform::default(), Blend::None, Color::WHITE).unwrap();
+        let encoded = path.data().encoded().as_ptr() as *mut c_void;
+        assert_eq!(path.raw_mut().path, encoded);
+        assert_eq!(path.raw_mut().path_length as usize, path.data().encoded().len() * 4);
+    }
 }
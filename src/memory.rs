@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// GPU memory in use, as seen by the driver and by this crate
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Contiguous memory the driver can still hand out, in bytes
+    pub available: u32,
+    /// Bytes held by the buffers from [`Buffer::allocate`]
+    pub allocated: u64,
+    /// Buffers from [`Buffer::allocate`] still alive
+    pub buffers: usize,
+    /// Highest `allocated` so far
+    pub peak: u64,
+    /// See [`Context::set_memory_budget`]
+    pub budget: Option<u64>
+}
+
+/// Holder of GPU buffers it can drop and create again later, e.g. rendered glyphs or decoded images
+pub trait MemoryCache {
+    /// Free at least `bytes` if possible, returns the bytes actually freed
+    fn evict(&mut self, bytes: u64) -> u64;
+}
+
+struct Accounting {
+    allocated: u64,
+    buffers: usize,
+    peak: u64,
+    budget: Option<u64>
+}
+
+static ACCOUNTING: Mutex<Accounting> = Mutex::new(Accounting { allocated: 0, buffers: 0, peak: 0, budget: None });
+
+/// Cache the budget can evict through a shared reference
+pub(crate) trait SharedCache {
+    /// Like [`MemoryCache::evict`], 0 if the cache is busy
+    fn evict_shared(&self, bytes: u64) -> u64;
+}
+
+impl<C: MemoryCache> SharedCache for RefCell<C> {
+    fn evict_shared(&self, bytes: u64) -> u64 {
+        // a cache allocating from its own eviction is left alone
+        match self.try_borrow_mut() {
+            Ok(mut cache) => cache.evict(bytes),
+            Err(_) => 0
+        }
+    }
+}
+
+thread_local! {
+    /// Asked for memory in registration order, dropped caches are skipped
+    static CACHES: RefCell<Vec<Weak<dyn SharedCache>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn accounting() -> MutexGuard<'static, Accounting> {
+    ACCOUNTING.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Bytes of the planes of a buffer with rows padded to [`ADDRESS_ALIGNMENT`] like `vg_lite_allocate`,
+/// both reserved before allocating and tracked after so that the two always agree
+pub(crate) fn estimate(width: u32, height: u32, format: Format) -> u64 {
+    format.planes(width, height).iter()
+        .map(|layout| (layout.row_bytes as u64).next_multiple_of(ADDRESS_ALIGNMENT as u64) * layout.rows as u64)
+        .sum()
+}
+
+/// Ask the caches for `bytes`, returns the bytes they freed
+pub(crate) fn evict(bytes: u64) -> u64 {
+    let caches = CACHES.with(|caches| {
+        let mut caches = caches.borrow_mut();
+        caches.retain(|cache| cache.strong_count() > 0);
+        caches.clone()
+    });
+    let mut freed = 0;
+    for cache in caches.iter().filter_map(Weak::upgrade) {
+        if freed >= bytes {
+            break;
+        }
+        freed += cache.evict_shared(bytes - freed);
+    }
+    freed
+}
+
+pub(crate) fn register(cache: Weak<dyn SharedCache>) {
+    CACHES.with(|caches| caches.borrow_mut().push(cache));
+}
+
+/// Make room for `bytes` within the budget, evicting caches if needed
+pub(crate) fn reserve(bytes: u64) -> Result<(), Error> {
+    let over = || {
+        let accounting = accounting();
+        accounting.budget.map_or(0, |budget| (accounting.allocated + bytes).saturating_sub(budget))
+    };
+    let needed = over();
+    if needed > 0 {
+        evict(needed);
+        // evicted buffers the GPU may still read are only untracked once it is done with them
+        if over() > 0 && inflight::deferred() > 0 {
+            inflight::finish()?;
+        }
+        if over() > 0 {
+            return Err(Error::OutOfMemory);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn track(bytes: u64) {
+    let mut accounting = accounting();
+    accounting.allocated += bytes;
+    accounting.buffers += 1;
+    accounting.peak = accounting.peak.max(accounting.allocated);
+}
+
+pub(crate) fn untrack(bytes: u64) {
+    let mut accounting = accounting();
+    accounting.allocated -= bytes;
+    accounting.buffers -= 1;
+}
+
+impl Context {
+    pub fn memory_stats(&self) -> Result<MemoryStats, Error> {
+        let mut available = 0;
+        wrap_result(driver(|| unsafe { vg_lite_get_mem_size(&mut available) }), ())?;
+        let accounting = accounting();
+        Ok(MemoryStats {
+            available,
+            allocated: accounting.allocated,
+            buffers: accounting.buffers,
+            peak: accounting.peak,
+            budget: accounting.budget
+        })
+    }
+
+    /// Keep the buffers from [`Buffer::allocate`] under `budget` bytes: past it the registered
+    /// caches are evicted, then allocations fail with [`Error::OutOfMemory`]
+    pub fn set_memory_budget(&self, budget: Option<u64>) {
+        accounting().budget = budget;
+    }
+
+    /// Evict `cache` when an allocation goes over the budget or the driver runs out of memory,
+    /// until it is dropped. Caches are only asked on the thread that registered them
+    pub fn register_cache<C: MemoryCache + 'static>(&self, cache: &Rc<RefCell<C>>) {
+        let cache: Weak<RefCell<C>> = Rc::downgrade(cache);
+        register(cache);
+    }
+}
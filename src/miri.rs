@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+//! Fake driver for `cargo miri test path_`, Miri can't call into libvg_lite. Only the entry points
+//! of a context, of path uploads and of path draws are here, uploads are Rust allocations so that
+//! Miri reports an upload freed twice or never, draws read the whole path data
+use std::ptr::null_mut;
+
+use crate::vg_lite::*;
+
+#[no_mangle]
+extern "C" fn vg_lite_init(_tess_width: vg_lite_int32_t, _tess_height: vg_lite_int32_t) -> vg_lite_error_t {
+    vg_lite_error_VG_LITE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn vg_lite_close() -> vg_lite_error_t {
+    vg_lite_error_VG_LITE_SUCCESS
+}
+
+#[no_mangle]
+extern "C" fn vg_lite_finish() -> vg_lite_error_t {
+    vg_lite_error_VG_LITE_SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn vg_lite_draw(
+    _target: *mut vg_lite_buffer_t,
+    path: *mut vg_lite_path_t,
+    _fill_rule: vg_lite_fill_t,
+    _matrix: *mut vg_lite_matrix_t,
+    _blend: vg_lite_blend_t,
+    _color: vg_lite_color_t
+) -> vg_lite_error_t {
+    let path = &*path;
+    let data = std::slice::from_raw_parts(path.path as *const u8, path.path_length as usize);
+    std::hint::black_box(data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+    vg_lite_error_VG_LITE_SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn vg_lite_upload_path(path: *mut vg_lite_path_t) -> vg_lite_error_t {
+    static mut ADDRESS: u32 = 0x1000;
+    let path = &mut *path;
+    let data = std::slice::from_raw_parts(path.path as *const u8, path.path_length as usize);
+    let copy = Box::into_raw(data.to_vec().into_boxed_slice());
+    ADDRESS += 0x1000;
+    path.uploaded.handle = copy as *mut _;
+    path.uploaded.memory = copy as *mut _;
+    path.uploaded.address = ADDRESS;
+    path.uploaded.bytes = path.path_length;
+    vg_lite_error_VG_LITE_SUCCESS
+}
+
+#[no_mangle]
+unsafe extern "C" fn vg_lite_clear_path(path: *mut vg_lite_path_t) -> vg_lite_error_t {
+    let uploaded = &mut (*path).uploaded;
+    if !uploaded.handle.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(uploaded.handle as *mut u8, uploaded.bytes as usize)));
+    }
+    (uploaded.handle, uploaded.memory, uploaded.address, uploaded.bytes) = (null_mut(), null_mut(), 0, 0);
+    vg_lite_error_VG_LITE_SUCCESS
+}
@@ -1,18 +1,32 @@
 use std::mem::{transmute, size_of};
+use std::ops::{Deref, DerefMut};
 use std::os::raw::c_void;
 
 use crate::vg_lite::*;
 use crate::*;
 
 pub struct BBoxUnit(f32);
-pub trait OpCodeFormat: Sized + Clone + Copy + PartialOrd + Default + Into<BBoxUnit> {
+pub trait OpCodeFormat: Sized + Clone + Copy + PartialOrd + Default + Into<BBoxUnit> + 'static {
+    /// Smallest and largest coordinate
+    const MIN: f64;
+    const MAX: f64;
     fn format() -> DataFormat;
     fn transmute(op: u32) -> Self;
+    /// Opcode stored by [`OpCodeFormat::transmute`]
+    fn opcode(self) -> u32;
+    fn to_f64(self) -> f64;
+    /// Nearest coordinate, saturating
+    fn from_f64(value: f64) -> Self;
 }
 
 impl OpCodeFormat for i8 {
+    const MIN: f64 = i8::MIN as f64;
+    const MAX: f64 = i8::MAX as f64;
     fn format() -> DataFormat { DataFormat::I8 }
     fn transmute(op: u32) -> Self { op as i8 }
+    fn opcode(self) -> u32 { self as u32 }
+    fn to_f64(self) -> f64 { self as f64 }
+    fn from_f64(value: f64) -> Self { value as i8 }
 }
 impl Into<BBoxUnit> for i8 {
     fn into(self) -> BBoxUnit {
@@ -20,8 +34,13 @@ impl Into<BBoxUnit> for i8 {
     }
 }
 impl OpCodeFormat for i16 {
+    const MIN: f64 = i16::MIN as f64;
+    const MAX: f64 = i16::MAX as f64;
     fn format() -> DataFormat { DataFormat::I16 }
     fn transmute(op: u32) -> Self { op as i16 }
+    fn opcode(self) -> u32 { self as u32 }
+    fn to_f64(self) -> f64 { self as f64 }
+    fn from_f64(value: f64) -> Self { value as i16 }
 }
 impl Into<BBoxUnit> for i16 {
     fn into(self) -> BBoxUnit {
@@ -29,8 +48,13 @@ impl Into<BBoxUnit> for i16 {
     }
 }
 impl OpCodeFormat for i32 {
+    const MIN: f64 = i32::MIN as f64;
+    const MAX: f64 = i32::MAX as f64;
     fn format() -> DataFormat { DataFormat::I32 }
     fn transmute(op: u32) -> Self { op as i32 }
+    fn opcode(self) -> u32 { self as u32 }
+    fn to_f64(self) -> f64 { self as f64 }
+    fn from_f64(value: f64) -> Self { value as i32 }
 }
 impl Into<BBoxUnit> for i32 {
     fn into(self) -> BBoxUnit {
@@ -38,10 +62,15 @@ impl Into<BBoxUnit> for i32 {
     }
 }
 impl OpCodeFormat for f32 {
+    const MIN: f64 = f32::MIN as f64;
+    const MAX: f64 = f32::MAX as f64;
     fn format() -> DataFormat { DataFormat::F32 }
     fn transmute(op: u32) -> Self {
         unsafe { transmute(op as u32) }
     }
+    fn opcode(self) -> u32 { self.to_bits() }
+    fn to_f64(self) -> f64 { self as f64 }
+    fn from_f64(value: f64) -> Self { value as f32 }
 }
 impl Into<BBoxUnit> for f32 {
     fn into(self) -> BBoxUnit {
@@ -152,56 +181,34 @@ impl Into<vg_lite_fill> for Fill {
 #[derive(Debug, Clone)]
 pub struct PathData<T: OpCodeFormat> {
     data: Vec<T>,
-    min_x: T,
-    min_y: T,
-    max_x: T,
-    max_y: T
+    /// Set by [`PathData::set_bbox`], computed from the data otherwise
+    bbox: Option<[T; 4]>
 }
 
 impl<T: OpCodeFormat> PathData<T> {
     pub fn append(&mut self, op: Opcode<T>) -> &mut Self {
-        let data = &mut self.data;
-        match op {
-            Opcode::End => { data.push(T::transmute(VLC_OP_END)) }
-            Opcode::Close => { data.push(T::transmute(VLC_OP_CLOSE)); }
-            Opcode::Move { x, y } => {
-                // FIXME
-                self.min_x = x;
-                self.max_x = x;
-                self.min_y = y;
-                self.max_y = y;
-
-                data.push(T::transmute(VLC_OP_MOVE));
-                data.push(x);
-                data.push(y);
-            }
-            Opcode::Line { x, y } => {
-                self.min_x = if self.min_x > x { x } else { self.min_x };
-                self.min_y = if self.min_y > y { y } else { self.min_y };
-                self.max_x = if self.max_x < x { x } else { self.max_x };
-                self.max_y = if self.max_y < y { y } else { self.max_y };
-
-                data.push(T::transmute(VLC_OP_LINE));
-                data.push(x);
-                data.push(y);
-            }
-            Opcode::Cubic { cx1, cy1, cx2, cy2, x, y } => {
-                self.min_x = if self.min_x > x { x } else { self.min_x };
-                self.min_y = if self.min_y > y { y } else { self.min_y };
-                self.max_x = if self.max_x < x { x } else { self.max_x };
-                self.max_y = if self.max_y < y { y } else { self.max_y };
-                // TODO:
-
-                data.push(T::transmute(VLC_OP_CUBIC));
-                data.push(cx1);
-                data.push(cy1);
-                data.push(cx2);
-                data.push(cy2);
-                data.push(x);
-                data.push(y);
-            }
-            _ => ()
+        let (code, arguments) = match op {
+            Opcode::End => (VLC_OP_END, vec![]),
+            Opcode::Close => (VLC_OP_CLOSE, vec![]),
+            Opcode::Move { x, y } => (VLC_OP_MOVE, vec![x, y]),
+            Opcode::MoveRel { dx, dy } => (VLC_OP_MOVE_REL, vec![dx, dy]),
+            Opcode::Line { x, y } => (VLC_OP_LINE, vec![x, y]),
+            Opcode::LineRel { dx, dy } => (VLC_OP_LINE_REL, vec![dx, dy]),
+            Opcode::Quad { cx, cy, x, y } => (VLC_OP_QUAD, vec![cx, cy, x, y]),
+            Opcode::QuadRel { dcx, dcy, dx, dy } => (VLC_OP_QUAD_REL, vec![dcx, dcy, dx, dy]),
+            Opcode::Cubic { cx1, cy1, cx2, cy2, x, y } => (VLC_OP_CUBIC, vec![cx1, cy1, cx2, cy2, x, y]),
+            Opcode::CubicRel { dcx1, dcy1, dcx2, dcy2, dx, dy } => (VLC_OP_CUBIC_REL, vec![dcx1, dcy1, dcx2, dcy2, dx, dy]),
+            Opcode::SCCWArc { rh, rv, rot, x, y } => (VLC_OP_SCCWARC, vec![rh, rv, rot, x, y]),
+            Opcode::SCCWArcRel { rh, rv, rot, x, y } => (VLC_OP_SCCWARC_REL, vec![rh, rv, rot, x, y]),
+            Opcode::SCWArc { rh, rv, rot, x, y } => (VLC_OP_SCWARC, vec![rh, rv, rot, x, y]),
+            Opcode::SCWArcRel { rh, rv, rot, x, y } => (VLC_OP_SCWARC_REL, vec![rh, rv, rot, x, y]),
+            Opcode::LCCWArc { rh, rv, rot, x, y } => (VLC_OP_LCCWARC, vec![rh, rv, rot, x, y]),
+            Opcode::LCCWArcRel { rh, rv, rot, x, y } => (VLC_OP_LCCWARC_REL, vec![rh, rv, rot, x, y]),
+            Opcode::LCWArc { rh, rv, rot, x, y } => (VLC_OP_LCWARC, vec![rh, rv, rot, x, y]),
+            Opcode::LCWArcRel { rh, rv, rot, x, y } => (VLC_OP_LCWARC_REL, vec![rh, rv, rot, x, y])
         };
+        self.data.push(T::transmute(code));
+        self.data.extend(arguments);
         self
     }
 
@@ -225,21 +232,104 @@ impl<T: OpCodeFormat> PathData<T> {
         self.append(Opcode::Cubic { cx1: x1, cy1: y1, cx2: x2, cy2: y2, x, y })
     }
 
+    /// Opcodes and coordinates as the GPU reads them
+    pub fn encoded(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Bounds of every point and control point, arcs count with the ellipse they may follow
     pub fn bounding_box(&self) -> [f32; 4] {
-        [self.min_x.into().0, self.min_y.into().0, self.max_x.into().0, self.max_y.into().0]
+        if let Some(bbox) = self.bbox {
+            return bbox.map(|value| value.into().0);
+        }
+        let mut bounds = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+        let mut include = |(x, y): (f64, f64)| {
+            bounds = [bounds[0].min(x), bounds[1].min(y), bounds[2].max(x), bounds[3].max(y)];
+        };
+        // current point, start of the subpath and last control point
+        let (mut current, mut start, mut control) = ((0., 0.), (0., 0.), (0., 0.));
+        let mut position = 0;
+        while let Some(op) = self.data.get(position) {
+            let op = op.opcode();
+            let Ok(kinds) = arguments(op) else { break };
+            let Some(values) = self.data.get(position + 1..position + 1 + kinds.len()) else { break };
+            position += 1 + kinds.len();
+            let a: Vec<f64> = values.iter().map(|value| value.to_f64()).collect();
+            let relative = matches!(kinds.last(), Some(Argument::Delta));
+            let point = |x: f64, y: f64| match relative {
+                true => (current.0 + x, current.1 + y),
+                false => (x, y)
+            };
+            let next = match op {
+                VLC_OP_END | VLC_OP_BREAK => current,
+                VLC_OP_CLOSE => start,
+                VLC_OP_HLINE => (a[0], current.1),
+                VLC_OP_HLINE_REL => (current.0 + a[0], current.1),
+                VLC_OP_VLINE => (current.0, a[0]),
+                VLC_OP_VLINE_REL => (current.0, current.1 + a[0]),
+                VLC_OP_MOVE | VLC_OP_MOVE_REL | VLC_OP_LINE | VLC_OP_LINE_REL => point(a[0], a[1]),
+                VLC_OP_QUAD | VLC_OP_QUAD_REL | VLC_OP_CUBIC | VLC_OP_CUBIC_REL => {
+                    for pair in a[..a.len() - 2].chunks(2) {
+                        control = point(pair[0], pair[1]);
+                        include(control);
+                    }
+                    point(a[a.len() - 2], a[a.len() - 1])
+                }
+                VLC_OP_SQUAD | VLC_OP_SQUAD_REL | VLC_OP_SCUBIC | VLC_OP_SCUBIC_REL => {
+                    // the first control point mirrors the previous one
+                    control = (2. * current.0 - control.0, 2. * current.1 - control.1);
+                    include(control);
+                    if a.len() == 4 {
+                        control = point(a[0], a[1]);
+                        include(control);
+                    }
+                    point(a[a.len() - 2], a[a.len() - 1])
+                }
+                _ => {
+                    // the ellipse holds both ends, the driver grows radii too small to join them
+                    let end = point(a[3], a[4]);
+                    let chord = ((end.0 - current.0).powi(2) + (end.1 - current.1).powi(2)).sqrt();
+                    let reach = 2. * a[0].abs().max(a[1].abs()).max(chord / 2.);
+                    include((current.0.max(end.0) - reach, current.1.max(end.1) - reach));
+                    include((current.0.min(end.0) + reach, current.1.min(end.1) + reach));
+                    end
+                }
+            };
+            if !matches!(op, VLC_OP_QUAD | VLC_OP_QUAD_REL | VLC_OP_CUBIC | VLC_OP_CUBIC_REL
+                | VLC_OP_SQUAD | VLC_OP_SQUAD_REL | VLC_OP_SCUBIC | VLC_OP_SCUBIC_REL) {
+                control = next;
+            }
+            if matches!(op, VLC_OP_MOVE | VLC_OP_MOVE_REL) {
+                start = next;
+            }
+            if !matches!(op, VLC_OP_END | VLC_OP_BREAK | VLC_OP_CLOSE) {
+                include(next);
+            }
+            current = next;
+        }
+        match bounds[0] <= bounds[2] {
+            true => bounds.map(|value| value as f32),
+            false => [0.; 4]
+        }
     }
 
     pub fn fill(self, quality: Quality) -> Path<T> {
         let bbox = self.bounding_box();
         let mut path = Path::new(self, quality);
-        path.path.path_type |= 0b10;
-        path.path.bounding_box[0] = bbox[0];
-        path.path.bounding_box[1] = bbox[1];
-        path.path.bounding_box[2] = bbox[2];
-        path.path.bounding_box[3] = bbox[3];
+        path.raw.path_type |= 0b10;
+        path.raw.bounding_box[0] = bbox[0];
+        path.raw.bounding_box[1] = bbox[1];
+        path.raw.bounding_box[2] = bbox[2];
+        path.raw.bounding_box[3] = bbox[3];
         path
     }
 
+    /// Remove every opcode, e.g. to build the next frame of an animated path
+    pub fn clear(&mut self) -> &mut Self {
+        *self = Self::default();
+        self
+    }
+
     // TODO: stroke
 }
 
@@ -247,35 +337,176 @@ impl<T: OpCodeFormat> Default for PathData<T> {
     fn default() -> Self {
         PathData {
             data: Vec::new(),
-            min_x: T::default(),
-            min_y: T::default(),
-            max_x: T::default(),
-            max_y: T::default()
+            bbox: None
         }
     }
 }
 
 impl<T: OpCodeFormat> PathData<T> {
+    /// Bounds to use instead of the ones computed from the data
     pub fn set_bbox(&mut self, min_x: T, min_y: T, max_x: T, max_y: T) {
-        self.min_x = min_x;
-        self.min_y = min_y;
-        self.max_x = max_x;
-        self.max_y = max_y;
+        self.bbox = Some([min_x, min_y, max_x, max_y]);
+    }
+
+    /// Kind of every value of the data, `None` for the opcodes
+    fn kinds(&self) -> Result<Vec<Option<Argument>>, Error> {
+        let mut kinds = Vec::with_capacity(self.data.len());
+        while kinds.len() < self.data.len() {
+            let arguments = arguments(self.data[kinds.len()].opcode())?;
+            kinds.push(None);
+            kinds.extend(arguments.iter().copied().map(Some));
+        }
+        if kinds.len() > self.data.len() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(kinds)
+    }
+
+    fn map<U: OpCodeFormat>(&self, convert: impl Fn(Argument, T) -> Result<U, Error>) -> Result<PathData<U>, Error> {
+        let data = self.kinds()?.into_iter().zip(&self.data)
+            .map(|(kind, value)| match kind {
+                Some(argument) => convert(argument, *value),
+                None => Ok(U::transmute(value.opcode()))
+            })
+            .collect::<Result<_, _>>()?;
+        let bbox = match self.bbox {
+            Some([min_x, min_y, max_x, max_y]) => Some([
+                convert(Argument::X, min_x)?, convert(Argument::Y, min_y)?,
+                convert(Argument::X, max_x)?, convert(Argument::Y, max_y)?
+            ]),
+            None => None
+        };
+        Ok(PathData { data, bbox })
+    }
+
+    /// Same path with coordinates of type `U`, fails with [`Error::Overflow`] if a coordinate is
+    /// out of the range of `U` and with [`Error::PrecisionLoss`] if `U` can't represent it exactly
+    pub fn convert<U: OpCodeFormat>(&self) -> Result<PathData<U>, Error> {
+        self.map(|_, value| exact(value.to_f64()))
+    }
+
+    /// Coordinates centered and scaled by a power of two to use the range of the integer type `U`,
+    /// e.g. with `i16` to halve the size of an `f32` path. The transform maps them back:
+    /// draw with `transform * compensation`. Fails with [`Error::PrecisionLoss`] if `U` can't keep
+    /// the coordinates to the unit, and if an arc rotation isn't a whole number of degrees
+    pub fn quantize<U: OpCodeFormat>(&self) -> Result<(PathData<U>, Transform), Error> {
+        if matches!(U::format(), DataFormat::F32) {
+            return Ok((self.convert()?, Transform::default()));
+        }
+        let kinds = self.kinds()?;
+        let bbox = self.bbox.into_iter()
+            .flat_map(|bbox| [Argument::X, Argument::Y, Argument::X, Argument::Y].into_iter().zip(bbox));
+        let (mut low, mut high, mut delta) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2], 0f64);
+        for (argument, value) in kinds.into_iter().zip(&self.data).filter_map(|(kind, value)| Some((kind?, *value))).chain(bbox) {
+            let value = value.to_f64();
+            if !value.is_finite() {
+                return Err(Error::Overflow);
+            }
+            match argument {
+                Argument::X | Argument::Y => {
+                    let axis = argument as usize;
+                    low[axis] = low[axis].min(value);
+                    high[axis] = high[axis].max(value);
+                }
+                Argument::Delta => delta = delta.max(value.abs()),
+                Argument::Angle => ()
+            }
+        }
+        let origin = [0, 1].map(|axis| match low[axis] <= high[axis] {
+            true => ((low[axis] + high[axis]) / 2.) as f32,
+            false => 0.
+        });
+        let span = [0, 1].into_iter()
+            .map(|axis| (high[axis] - origin[axis] as f64).max(origin[axis] as f64 - low[axis]))
+            .fold(delta, f64::max);
+        let mut scale = match span > 0. {
+            true => 2f64.powi(((U::MAX / span).log2().floor() as i32).clamp(-100, 100)),
+            false => 1.
+        };
+        while span * scale > U::MAX {
+            scale /= 2.;
+        }
+        if scale < 1. {
+            return Err(Error::PrecisionLoss);
+        }
+        let quantized = self.map(|argument, value| {
+            let value = value.to_f64();
+            exact(match argument {
+                Argument::X | Argument::Y => ((value - origin[argument as usize] as f64) * scale).round(),
+                Argument::Delta => (value * scale).round(),
+                Argument::Angle => value
+            })
+        })?;
+        let unit = (1. / scale) as f32;
+        let compensation = Transform { m: [
+            [unit, 0., origin[0]],
+            [0., unit, origin[1]],
+            [0., 0., 1.]
+        ]};
+        Ok((quantized, compensation))
     }
 }
 
-#[derive(Debug, Clone)]
+/// What an opcode argument is, to convert it
+#[derive(Debug, Clone, Copy)]
+enum Argument {
+    X = 0,
+    Y = 1,
+    /// Relative coordinate or radius
+    Delta,
+    /// Rotation of an arc in degrees
+    Angle
+}
+
+/// Arguments following an opcode
+fn arguments(op: u32) -> Result<&'static [Argument], Error> {
+    use Argument::*;
+    Ok(match op {
+        VLC_OP_END | VLC_OP_CLOSE | VLC_OP_BREAK => &[],
+        VLC_OP_MOVE | VLC_OP_LINE | VLC_OP_SQUAD => &[X, Y],
+        VLC_OP_MOVE_REL | VLC_OP_LINE_REL | VLC_OP_SQUAD_REL => &[Delta, Delta],
+        VLC_OP_QUAD | VLC_OP_SCUBIC => &[X, Y, X, Y],
+        VLC_OP_QUAD_REL | VLC_OP_SCUBIC_REL => &[Delta; 4],
+        VLC_OP_CUBIC => &[X, Y, X, Y, X, Y],
+        VLC_OP_CUBIC_REL => &[Delta; 6],
+        VLC_OP_HLINE => &[X],
+        VLC_OP_VLINE => &[Y],
+        VLC_OP_HLINE_REL | VLC_OP_VLINE_REL => &[Delta],
+        VLC_OP_SCCWARC | VLC_OP_SCWARC | VLC_OP_LCCWARC | VLC_OP_LCWARC => &[Delta, Delta, Angle, X, Y],
+        VLC_OP_SCCWARC_REL | VLC_OP_SCWARC_REL | VLC_OP_LCCWARC_REL | VLC_OP_LCWARC_REL => &[Delta, Delta, Angle, Delta, Delta],
+        _ => return Err(Error::InvalidArgument)
+    })
+}
+
+/// `value` as a `U`, unchanged
+fn exact<U: OpCodeFormat>(value: f64) -> Result<U, Error> {
+    if !value.is_finite() || value < U::MIN || value > U::MAX {
+        return Err(Error::Overflow);
+    }
+    let converted = U::from_f64(value);
+    if converted.to_f64() != value {
+        return Err(Error::PrecisionLoss);
+    }
+    Ok(converted)
+}
+
+/// Encoded path ready to draw. The driver reads the data of the path in place: it is only
+/// reachable through the path, and moving the path doesn't move it
+#[derive(Debug)]
 pub struct Path<T: OpCodeFormat> {
-    pub path: vg_lite_path,
-    #[allow(unused)]
-    /// Keep life cycle
-    data: PathData<T>
+    /// `path` points into the heap memory of `data`
+    raw: vg_lite_path,
+    data: PathData<T>,
+    /// Last submission drawing the path, see [`Context::in_flight`]
+    last_use: u64,
+    /// The data may have changed since `raw` was pointed at it, a [`PathEdit`] can be forgotten
+    editing: bool
 }
 
 impl<T: OpCodeFormat> Path<T> {
     pub fn new(data: PathData<T>, quality: Quality) -> Self {
         Self {
-            path: vg_lite_path {
+            raw: vg_lite_path {
                 bounding_box: [0.; 4],
                 quality: quality.into(),
                 format: T::format().into(),
@@ -298,6 +529,152 @@ impl<T: OpCodeFormat> Path<T> {
                 add_end: 0
             },
             data,
+            last_use: 0,
+            editing: false
+        }
+    }
+
+    pub fn data(&self) -> &PathData<T> {
+        &self.data
+    }
+
+    pub fn format(&self) -> DataFormat {
+        T::format()
+    }
+
+    /// Bounds used by the GPU, set by [`PathData::fill`] and [`Path::edit`]
+    pub fn bounding_box(&self) -> [f32; 4] {
+        match self.editing {
+            true => self.data.bounding_box(),
+            false => self.raw.bounding_box
+        }
+    }
+
+    /// For the driver, which only updates its own bookkeeping in it. Points it at the data
+    /// again if an edit wasn't committed
+    pub(crate) fn raw_mut(&mut self) -> &mut vg_lite_path {
+        if self.editing {
+            self.commit();
+        }
+        &mut self.raw
+    }
+
+    /// The GPU reads the path in the open submission
+    pub(crate) fn used(&mut self) {
+        self.last_use = inflight::submission();
+    }
+
+    /// Change the data, the path is updated when the guard is dropped. Data the GPU may still
+    /// read is kept until it is done, and an uploaded path is read from its data again
+    /// until the next [`Path::upload`]
+    pub fn edit(&mut self) -> PathEdit<'_, T> {
+        let in_flight = inflight::in_flight(self.last_use);
+        if in_flight || self.is_uploaded() {
+            let kept = match in_flight {
+                true => {
+                    let copy = self.data.clone();
+                    std::mem::replace(&mut self.data, copy)
+                }
+                false => PathData::default()
+            };
+            inflight::release(self.last_use, inflight::Release::ClearPath(self.raw, Box::new(kept)));
+            self.raw.uploaded = vg_lite_hw_memory {
+                handle: null_mut(),
+                memory: null_mut(),
+                address: 0,
+                bytes: 0,
+                property: self.raw.uploaded.property
+            };
+        }
+        // never left pointing at the kept data, even if the guard is forgotten
+        self.commit();
+        self.editing = true;
+        PathEdit { path: self }
+    }
+
+    /// Copy the data to GPU memory, draws read it from there until the path is edited or dropped
+    pub fn upload(&mut self) -> Result<(), Error> {
+        if self.is_uploaded() {
+            return Ok(());
+        }
+        let raw = self.raw_mut();
+        wrap_result(driver(|| unsafe { vg_lite_upload_path(raw) }), ())
+    }
+
+    pub fn is_uploaded(&self) -> bool {
+        !self.raw.uploaded.handle.is_null()
+    }
+
+    /// Let the driver upload the data by itself when the path is drawn, see [`Path::upload`]
+    pub fn set_auto_upload(&mut self, enabled: bool) {
+        match enabled {
+            true => self.raw.uploaded.property |= 1,
+            false => self.raw.uploaded.property &= !1
+        }
+    }
+
+    /// Point the driver at the data again after it changed
+    fn commit(&mut self) {
+        let data = &self.data.data;
+        self.raw.path = data.as_ptr() as *mut c_void;
+        self.raw.path_length = (data.len() * size_of::<T>()) as u32;
+        self.raw.bounding_box = self.data.bounding_box();
+        self.raw.path_changed = 1;
+        self.editing = false;
+    }
+}
+
+/// Access to the data of a [`Path`], see [`Path::edit`]
+pub struct PathEdit<'a, T: OpCodeFormat> {
+    path: &'a mut Path<T>
+}
+
+impl<T: OpCodeFormat> Deref for PathEdit<'_, T> {
+    type Target = PathData<T>;
+
+    fn deref(&self) -> &PathData<T> {
+        &self.path.data
+    }
+}
+
+impl<T: OpCodeFormat> DerefMut for PathEdit<'_, T> {
+    fn deref_mut(&mut self) -> &mut PathData<T> {
+        &mut self.path.data
+    }
+}
+
+impl<T: OpCodeFormat> Drop for PathEdit<'_, T> {
+    fn drop(&mut self) {
+        self.path.commit();
+    }
+}
+
+impl<T: OpCodeFormat> Clone for Path<T> {
+    /// Copy of the data with the settings of the path, uploaded again if the original is
+    fn clone(&self) -> Self {
+        let mut path = Path::new(self.data.clone(), Quality::High);
+        path.raw.quality = self.raw.quality;
+        path.raw.bounding_box = self.raw.bounding_box;
+        path.raw.path_type = self.raw.path_type;
+        path.raw.add_end = self.raw.add_end;
+        path.raw.path_changed = self.raw.path_changed;
+        path.raw.uploaded.property = self.raw.uploaded.property;
+        if self.is_uploaded() {
+            // drawn from its data if the upload fails
+            let _ = path.upload();
+        }
+        path
+    }
+}
+
+impl<T: OpCodeFormat> Drop for Path<T> {
+    fn drop(&mut self) {
+        // the driver may still read the data after the path is gone
+        if self.last_use == 0 && self.raw.uploaded.handle.is_null() {
+            // the driver never saw the path
+            return;
         }
+        let data = std::mem::take(&mut self.data);
+        inflight::release(self.last_use, inflight::Release::ClearPath(self.raw, Box::new(data)));
     }
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+
+use crate::vg_lite::*;
+use crate::*;
+
+type Key = (u32, u32, Format);
+
+/// Recycles [`Buffer`]s of the same size and format instead of freeing and allocating them again,
+/// e.g. for the offscreen buffers of an animation. Buffers come back when their [`BufferLease`]
+/// is dropped, and are kept until the idle ones exceed the high-water mark
+pub struct BufferPool {
+    idle: RefCell<HashMap<Key, Vec<Buffer<'static>>>>,
+    /// Idle buffers from the least to the most recently returned, trimmed first to last
+    order: RefCell<Vec<Key>>,
+    idle_bytes: Cell<u64>,
+    high_water: Cell<u64>
+}
+
+/// A buffer from a [`BufferPool`], going back to it on drop
+pub struct BufferLease<'a> {
+    pool: &'a BufferPool,
+    buffer: Option<Buffer<'static>>
+}
+
+impl BufferPool {
+    /// Keep at most `high_water` bytes of idle buffers
+    pub fn new(high_water: u64) -> Self {
+        BufferPool {
+            idle: RefCell::default(),
+            order: RefCell::default(),
+            idle_bytes: Cell::new(0),
+            high_water: Cell::new(high_water)
+        }
+    }
+
+    /// An idle buffer of this size and format, or a newly allocated one.
+    /// The pixels are whatever the previous lease left
+    pub fn acquire(&self, width: u32, height: u32, format: Format) -> Result<BufferLease<'_>, Error> {
+        let key = (width, height, format);
+        let recycled = self.idle.borrow_mut().get_mut(&key).and_then(Vec::pop);
+        let buffer = match recycled {
+            Some(buffer) => {
+                let mut order = self.order.borrow_mut();
+                if let Some(i) = order.iter().rposition(|k| *k == key) {
+                    order.remove(i);
+                }
+                self.idle_bytes.set(self.idle_bytes.get() - buffer.allocated_bytes());
+                buffer
+            }
+            None => Buffer::allocate(width, height, format)?
+        };
+        Ok(BufferLease { pool: self, buffer: Some(buffer) })
+    }
+
+    fn release(&self, mut buffer: Buffer<'static>) {
+        let bytes = buffer.allocated_bytes();
+        if bytes > self.high_water.get() {
+            return;
+        }
+        let fresh = vg_lite_buffer::default();
+        buffer.buffer.image_mode = fresh.image_mode;
+        buffer.buffer.transparency_mode = fresh.transparency_mode;
+        buffer.buffer.yuv.yuv2rgb = fresh.yuv.yuv2rgb;
+        buffer.buffer.yuv.swizzle = fresh.yuv.swizzle;
+        buffer.buffer.index_endian = fresh.index_endian;
+        buffer.palette.clear();
+        let key = (buffer.width(), buffer.height(), buffer.format());
+        self.idle.borrow_mut().entry(key).or_default().push(buffer);
+        self.order.borrow_mut().push(key);
+        self.idle_bytes.set(self.idle_bytes.get() + bytes);
+        self.trim(self.high_water.get());
+    }
+
+    /// Free the least recently returned idle buffers until at most `bytes` are kept
+    pub fn trim(&self, bytes: u64) {
+        let mut idle = self.idle.borrow_mut();
+        let mut order = self.order.borrow_mut();
+        while self.idle_bytes.get() > bytes && !order.is_empty() {
+            let key = order.remove(0);
+            let buffers = idle.get_mut(&key).unwrap();
+            let buffer = buffers.remove(0);
+            if buffers.is_empty() {
+                idle.remove(&key);
+            }
+            self.idle_bytes.set(self.idle_bytes.get() - buffer.allocated_bytes());
+        }
+    }
+
+    /// Free every idle buffer, leased ones still come back
+    pub fn clear(&self) {
+        self.trim(0);
+    }
+
+    pub fn high_water(&self) -> u64 {
+        self.high_water.get()
+    }
+
+    pub fn set_high_water(&self, bytes: u64) {
+        self.high_water.set(bytes);
+        self.trim(bytes);
+    }
+
+    /// GPU memory held by idle buffers
+    pub fn idle_bytes(&self) -> u64 {
+        self.idle_bytes.get()
+    }
+
+    pub fn idle_count(&self) -> usize {
+        self.order.borrow().len()
+    }
+}
+
+impl memory::SharedCache for BufferPool {
+    fn evict_shared(&self, bytes: u64) -> u64 {
+        // the pool is busy if it allocates from its own eviction
+        if self.idle.try_borrow_mut().is_err() || self.order.try_borrow_mut().is_err() {
+            return 0;
+        }
+        let before = self.idle_bytes();
+        self.trim(before.saturating_sub(bytes));
+        before - self.idle_bytes()
+    }
+}
+
+impl Context {
+    /// Trim `pool` when an allocation goes over the budget or the driver runs out of memory,
+    /// until it is dropped. Leased buffers are left alone, see [`Context::register_cache`]
+    pub fn register_pool(&self, pool: &Rc<BufferPool>) {
+        let pool: Weak<BufferPool> = Rc::downgrade(pool);
+        memory::register(pool);
+    }
+}
+
+impl BufferLease<'_> {
+    /// Take the buffer out of the pool for good
+    pub fn detach(mut self) -> Buffer<'static> {
+        self.buffer.take().unwrap()
+    }
+}
+
+impl Deref for BufferLease<'_> {
+    type Target = Buffer<'static>;
+
+    fn deref(&self) -> &Buffer<'static> {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for BufferLease<'_> {
+    fn deref_mut(&mut self) -> &mut Buffer<'static> {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl Drop for BufferLease<'_> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.release(buffer);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// Gamma conversion of the colors written to the target
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Gamma {
+    /// Colors are written as they are
+    #[default]
+    NoConversion,
+    /// sRGB colors are converted to linear space
+    Linear,
+    /// Linear colors are converted to sRGB space
+    NonLinear
+}
+
+impl From<Gamma> for vg_lite_gamma_conversion {
+    fn from(gamma: Gamma) -> Self {
+        match gamma {
+            Gamma::NoConversion => vg_lite_gamma_conversion_VG_LITE_GAMMA_NO_CONVERSION,
+            Gamma::Linear => vg_lite_gamma_conversion_VG_LITE_GAMMA_LINEAR,
+            Gamma::NonLinear => vg_lite_gamma_conversion_VG_LITE_GAMMA_NON_LINEAR
+        }
+    }
+}
+
+/// Vertical orientation of the target, `BottomTop` flips everything drawn upside down
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    #[default]
+    TopBottom,
+    BottomTop
+}
+
+impl From<Mirror> for vg_lite_orientation {
+    fn from(mirror: Mirror) -> Self {
+        match mirror {
+            Mirror::TopBottom => vg_lite_orientation_VG_LITE_ORIENTATION_TOP_BOTTOM,
+            Mirror::BottomTop => vg_lite_orientation_VG_LITE_ORIENTATION_BOTTOM_TOP
+        }
+    }
+}
+
+/// Whether sources and targets hold premultiplied colors
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Premultiply {
+    pub source: bool,
+    pub dest: bool
+}
+
+pub(crate) fn require(feature: vg_lite_feature) -> Result<(), Error> {
+    match has_feature(feature) {
+        true => Ok(()),
+        false => Err(Error::NotSupport)
+    }
+}
+
+impl Context {
+    /// Dithering of the colors written to the target, against banding of gradients on
+    /// [`Format::RGB565`] panels. Off by default
+    pub fn set_dither(&self, dither: bool) -> Result<(), Error> {
+        require(vg_lite_feature_gcFEATURE_BIT_VG_DITHER)?;
+        wrap_result(driver(|| unsafe {
+            match dither {
+                true => vg_lite_enable_dither(),
+                false => vg_lite_disable_dither()
+            }
+        }), ())?;
+        self.track(|state| state.dither = dither);
+        Ok(())
+    }
+
+    pub fn dither(&self) -> bool {
+        self.state.borrow().dither
+    }
+
+    pub fn set_gamma(&self, gamma: Gamma) -> Result<(), Error> {
+        require(vg_lite_feature_gcFEATURE_BIT_VG_GAMMA)?;
+        wrap_result(driver(|| unsafe { vg_lite_set_gamma(gamma.into()) }), ())?;
+        self.track(|state| state.gamma = gamma);
+        Ok(())
+    }
+
+    pub fn gamma(&self) -> Gamma {
+        self.state.borrow().gamma
+    }
+
+    pub fn set_mirror(&self, mirror: Mirror) -> Result<(), Error> {
+        require(vg_lite_feature_gcFEATURE_BIT_VG_MIRROR)?;
+        wrap_result(driver(|| unsafe { vg_lite_set_mirror(mirror.into()) }), ())?;
+        self.track(|state| state.mirror = mirror);
+        Ok(())
+    }
+
+    pub fn mirror(&self) -> Mirror {
+        self.state.borrow().mirror
+    }
+
+    pub fn set_premultiply(&self, premultiply: Premultiply) -> Result<(), Error> {
+        require(vg_lite_feature_gcFEATURE_BIT_VG_HW_PREMULTIPLY)?;
+        wrap_result(driver(|| unsafe { vg_lite_set_premultiply(premultiply.source as u8, premultiply.dest as u8) }), ())?;
+        self.track(|state| state.premultiply = premultiply);
+        Ok(())
+    }
+
+    pub fn premultiply(&self) -> Premultiply {
+        self.state.borrow().premultiply
+    }
+}
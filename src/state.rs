@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::ptr::null_mut;
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Every global setting of the GPU, which the driver keeps for the whole process.
+/// Drawing code describes what it needs here and applies it with [`Context::with_state`]
+/// instead of inheriting whatever the previous draw left behind
+#[derive(Clone, Default)]
+pub struct RenderState<'a> {
+    /// Draws only touch this rectangle of the target
+    pub scissor: Option<Rectangle>,
+    /// [`Format::A8`] buffer of the size of the target, its alpha multiplies every draw.
+    /// The GPU only reads it
+    pub mask: Option<&'a Buffer<'a>>,
+    pub source_alpha: GlobalAlpha,
+    pub dest_alpha: GlobalAlpha,
+    pub color_key: ColorKey,
+    pub pixel_matrix: Option<(ColorMatrix, PixelChannels)>,
+    pub color_transform: Option<ColorTransform>,
+    pub dither: bool,
+    pub gamma: Gamma,
+    pub mirror: Mirror,
+    pub premultiply: Premultiply
+}
+
+/// Failure of [`Context::with_state`], `result` is what the draw returned if it ran before
+/// the previous settings failed to come back
+#[derive(Debug)]
+pub struct StateError<R> {
+    pub error: Error,
+    pub result: Option<R>
+}
+
+impl<R> From<StateError<R>> for Error {
+    fn from(error: StateError<R>) -> Self {
+        error.error
+    }
+}
+
+/// Puts the settings from before [`Context::with_state`] back, also when the draw panics
+struct Restore<'a> {
+    context: &'a Context,
+    previous: RenderState<'static>,
+    previous_mask: *mut vg_lite_buffer,
+    done: bool
+}
+
+impl Restore<'_> {
+    fn restore(&mut self) -> Result<(), Error> {
+        self.done = true;
+        self.context.apply_state(&self.previous, self.previous_mask)
+    }
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.restore();
+        }
+    }
+}
+
+fn rect(rect: Option<Rectangle>) -> Option<(i32, i32, i32, i32)> {
+    rect.map(|r| (r.x, r.y, r.width, r.height))
+}
+
+fn apply_scissor(scissor: Option<Rectangle>) -> Result<(), Error> {
+    require(vg_lite_feature_gcFEATURE_BIT_VG_SCISSOR)?;
+    driver(|| unsafe {
+        match scissor {
+            Some(r) => {
+                wrap_result(vg_lite_set_scissor(r.x, r.y, r.x + r.width, r.y + r.height), ())?;
+                wrap_result(vg_lite_enable_scissor(), ())
+            }
+            None => wrap_result(vg_lite_disable_scissor(), ())
+        }
+    })
+}
+
+fn apply_mask(mask: *mut vg_lite_buffer) -> Result<(), Error> {
+    require(vg_lite_feature_gcFEATURE_BIT_VG_MASK)?;
+    driver(|| unsafe {
+        match mask.is_null() {
+            true => wrap_result(vg_lite_disable_masklayer(), ()),
+            false => {
+                wrap_result(vg_lite_set_masklayer(mask), ())?;
+                wrap_result(vg_lite_enable_masklayer(), ())
+            }
+        }
+    })
+}
+
+impl Context {
+    pub(crate) fn track<R>(&self, change: impl FnOnce(&mut RenderState<'static>) -> R) -> R {
+        change(&mut self.state.borrow_mut())
+    }
+
+    /// Global settings as last set through this context, without the mask layer
+    pub fn render_state(&self) -> RenderState<'static> {
+        self.state.borrow().clone()
+    }
+
+    /// Apply the fields of `state` that differ from the current ones
+    fn apply_state(&self, state: &RenderState, mask: *mut vg_lite_buffer) -> Result<(), Error> {
+        let current = self.render_state();
+        if rect(state.scissor) != rect(current.scissor) {
+            apply_scissor(state.scissor)?;
+            self.track(|tracked| tracked.scissor = state.scissor);
+        }
+        if mask != self.mask.get() {
+            apply_mask(mask)?;
+            self.mask.set(mask);
+        }
+        if state.source_alpha != current.source_alpha {
+            alpha::set(self, alpha::Side::Source, state.source_alpha)?;
+        }
+        if state.dest_alpha != current.dest_alpha {
+            alpha::set(self, alpha::Side::Dest, state.dest_alpha)?;
+        }
+        if state.color_key != current.color_key {
+            color_key::set(self, &state.color_key)?;
+        }
+        if state.pixel_matrix != current.pixel_matrix {
+            color_matrix::set(self, state.pixel_matrix)?;
+        }
+        if state.color_transform != current.color_transform {
+            color_transform::set(self, state.color_transform)?;
+        }
+        if state.dither != current.dither {
+            self.set_dither(state.dither)?;
+        }
+        if state.gamma != current.gamma {
+            self.set_gamma(state.gamma)?;
+        }
+        if state.mirror != current.mirror {
+            self.set_mirror(state.mirror)?;
+        }
+        if state.premultiply != current.premultiply {
+            self.set_premultiply(state.premultiply)?;
+        }
+        Ok(())
+    }
+
+    /// Run `draw` with the global settings of `state`, then put the previous ones back, even if
+    /// `draw` panics. Only the settings that differ are sent to the GPU, both ways
+    pub fn with_state<R>(&self, state: &RenderState, draw: impl FnOnce(&Context) -> R) -> Result<R, StateError<R>> {
+        let mut restore = Restore { context: self, previous: self.render_state(), previous_mask: self.mask.get(), done: false };
+        // `vg_lite_set_masklayer` takes a mutable pointer but only records the address of the mask
+        // for the draws, which sample it like a texture. Nothing writes through it
+        let mask = state.mask.map_or(null_mut(), |mask| &mask.buffer as *const _ as *mut _);
+        self.apply_state(state, mask).map_err(|error| StateError { error, result: None })?;
+        let result = draw(self);
+        if let Some(mask) = state.mask {
+            mask.used();
+        }
+        match restore.restore() {
+            Ok(()) => Ok(result),
+            Err(error) => Err(StateError { error, result: Some(result) })
+        }
+    }
+}
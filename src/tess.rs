@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::vg_lite::*;
+use crate::*;
+
+/// Tessellation window of the initialized driver, `(0, 0)` while it is closed
+static WINDOW: Mutex<(u32, u32)> = Mutex::new((0, 0));
+
+pub(crate) fn set_window(width: u32, height: u32) {
+    *WINDOW.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = (width, height);
+}
+
+/// See [`Context::set_tess_window_check`]
+static CHECK: AtomicBool = AtomicBool::new(false);
+
+fn window() -> (u32, u32) {
+    *WINDOW.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Fail draws whose path, once transformed and clipped to the target, is larger than the window,
+/// if enabled by [`Context::set_tess_window_check`]
+pub(crate) fn check_window(target: &Buffer, bbox: [f32; 4], transform: &Transform) -> Result<(), Error> {
+    let (width, height) = window();
+    if width == 0 || height == 0 || !CHECK.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let m = &transform.m;
+    let corners = [(bbox[0], bbox[1]), (bbox[2], bbox[1]), (bbox[0], bbox[3]), (bbox[2], bbox[3])].map(|(x, y)| {
+        let w = m[2][0] * x + m[2][1] * y + m[2][2];
+        ((m[0][0] * x + m[0][1] * y + m[0][2]) / w, (m[1][0] * x + m[1][1] * y + m[1][2]) / w)
+    });
+    let span = |axis: fn(&(f32, f32)) -> f32, size: u32| {
+        let low = corners.iter().map(axis).fold(f32::INFINITY, f32::min).max(0.);
+        let high = corners.iter().map(axis).fold(f32::NEG_INFINITY, f32::max).min(size as f32);
+        (high - low).ceil().max(0.) as u32
+    };
+    if span(|c| c.0, target.width()) > width || span(|c| c.1, target.height()) > height {
+        return Err(Error::TessWindowExceeded);
+    }
+    Ok(())
+}
+
+impl Context {
+    /// Size passed to [`Context::new`] or [`Context::reinit`], the largest area one draw can cover
+    pub fn tess_window(&self) -> (u32, u32) {
+        window()
+    }
+
+    /// Fail draws covering more than the tessellation window with [`Error::TessWindowExceeded`],
+    /// e.g. to grow it with [`Context::grow_tess_window`] first. Off by default, the driver then
+    /// splits such draws across several passes of the window
+    pub fn set_tess_window_check(&self, enabled: bool) {
+        CHECK.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Place the command buffer in `size` bytes at the physical address `physical`, e.g. in SRAM.
+    /// `physical` must be aligned on [`ADDRESS_ALIGNMENT`] bytes
+    pub fn set_command_buffer(&self, physical: u32, size: u32) -> Result<(), Error> {
+        if !physical.is_multiple_of(ADDRESS_ALIGNMENT) {
+            return Err(Error::NotAligned);
+        }
+        wrap_result(driver(|| unsafe { vg_lite_set_command_buffer(physical, size) }), ())
+    }
+
+    /// Place the tessellation buffer in `size` bytes at the physical address `physical`.
+    /// `physical` must be aligned on [`ADDRESS_ALIGNMENT`] bytes
+    pub fn set_tess_buffer(&self, physical: u32, size: u32) -> Result<(), Error> {
+        if !physical.is_multiple_of(ADDRESS_ALIGNMENT) {
+            return Err(Error::NotAligned);
+        }
+        wrap_result(driver(|| unsafe { vg_lite_set_tess_buffer(physical, size) }), ())
+    }
+
+    /// Close and initialize the driver again with another tessellation window, once the queued
+    /// draws are done. Every global setting goes back to its default, buffers placed with
+    /// [`Context::set_command_buffer`] and [`Context::set_tess_buffer`] have to be set again
+    pub fn reinit(&mut self, tess_width: u32, tess_height: u32) -> Result<(), Error> {
+        self.finish()?;
+        self.stop_fences();
+        driver(|| unsafe { vg_lite_close(); });
+        set_window(0, 0);
+        *self.state.get_mut() = RenderState::default();
+        self.mask.set(std::ptr::null_mut());
+        wrap_result(driver(|| unsafe { vg_lite_init(tess_width as i32, tess_height as i32) }), ())?;
+        set_window(tess_width, tess_height);
+        Ok(())
+    }
+
+    /// Make the tessellation window at least `width * height`, e.g. when the target grows.
+    /// Returns whether the driver was initialized again, see [`Context::reinit`]
+    pub fn grow_tess_window(&mut self, width: u32, height: u32) -> Result<bool, Error> {
+        let (current_width, current_height) = self.tess_window();
+        if width <= current_width && height <= current_height {
+            return Ok(false);
+        }
+        self.reinit(width.max(current_width), height.max(current_height))?;
+        Ok(true)
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Mul;
+
 use crate::vg_lite::*;
 
 pub type Transform = vg_lite_matrix;
@@ -29,3 +31,14 @@ impl Default for Transform {
         ]}
     }
 }
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    /// `self * rhs` applies `rhs` first
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform { m: std::array::from_fn(|row| std::array::from_fn(|column| {
+            (0..3).map(|k| self.m[row][k] * rhs.m[k][column]).sum()
+        })) }
+    }
+}
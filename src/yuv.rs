@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+use crate::vg_lite::*;
+use crate::*;
+
+/// YUV to RGB conversion rule used when sampling a YUV buffer, video (limited) range
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum YuvStandard {
+    #[default]
+    Bt601,
+    Bt709
+}
+
+impl From<YuvStandard> for vg_lite_yuv2rgb {
+    fn from(standard: YuvStandard) -> Self {
+        match standard {
+            YuvStandard::Bt601 => vg_lite_yuv2rgb_VG_LITE_YUV601,
+            YuvStandard::Bt709 => vg_lite_yuv2rgb_VG_LITE_YUV709
+        }
+    }
+}
+
+impl YuvStandard {
+    /// `[[y], [u], [v]]` rows of the RGB to YUV matrix
+    fn forward(&self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Bt601 => [
+                [0.257, 0.504, 0.098],
+                [-0.148, -0.291, 0.439],
+                [0.439, -0.368, -0.071]
+            ],
+            Self::Bt709 => [
+                [0.183, 0.614, 0.062],
+                [-0.101, -0.339, 0.439],
+                [0.439, -0.399, -0.040]
+            ]
+        }
+    }
+
+    /// `(v to r, u to g, v to g, u to b)`
+    fn inverse(&self) -> (f32, f32, f32, f32) {
+        match self {
+            Self::Bt601 => (1.596, 0.392, 0.813, 2.017),
+            Self::Bt709 => (1.793, 0.213, 0.533, 2.112)
+        }
+    }
+
+    /// CPU reference of the hardware conversion, alpha is opaque
+    pub fn to_rgb(&self, y: u8, u: u8, v: u8) -> Color {
+        let (vr, ug, vg, ub) = self.inverse();
+        let y = 1.164 * (y as f32 - 16.);
+        let (u, v) = (u as f32 - 128., v as f32 - 128.);
+        let channel = |c: f32| c.round().clamp(0., 255.) as u8;
+        Color::rgb(channel(y + vr * v), channel(y - ug * u - vg * v), channel(y + ub * u))
+    }
+
+    /// `[y, u, v]` of a color, alpha is ignored
+    pub fn from_rgb(&self, color: Color) -> [u8; 3] {
+        let rgb = [color.r, color.g, color.b].map(|c| c as f32);
+        let offsets = [16., 128., 128.];
+        let mut yuv = [0; 3];
+        for (i, row) in self.forward().iter().enumerate() {
+            let value = offsets[i] + row.iter().zip(rgb).map(|(k, c)| k * c).sum::<f32>();
+            yuv[i] = value.round().clamp(0., 255.) as u8;
+        }
+        yuv
+    }
+}
+
+/// Order of the chroma samples in interleaved planes, `Vu` turns NV12 into NV21
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UvSwizzle {
+    #[default]
+    Uv,
+    Vu
+}
+
+impl From<UvSwizzle> for vg_lite_swizzle {
+    fn from(swizzle: UvSwizzle) -> Self {
+        match swizzle {
+            UvSwizzle::Uv => vg_lite_swizzle_VG_LITE_SWIZZLE_UV,
+            UvSwizzle::Vu => vg_lite_swizzle_VG_LITE_SWIZZLE_VU
+        }
+    }
+}
+
+/// Role of one memory plane of a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    /// Pixels of single plane formats, luma of planar YUV, or packed YUYV
+    Primary,
+    /// Interleaved UV, or U alone for three plane formats
+    Chroma,
+    /// V of three plane formats
+    V,
+    /// Separate alpha of ANV12 and AYUY2
+    Alpha
+}
+
+/// Size of one plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaneLayout {
+    pub plane: Plane,
+    /// Bytes of pixel data per row, without padding
+    pub row_bytes: u32,
+    pub rows: u32
+}
+
+impl Format {
+    pub fn is_yuv(&self) -> bool {
+        matches!(self,
+            Self::NV12 | Self::ANV12 | Self::NV16 | Self::YV12 | Self::YV24 | Self::YUY2 | Self::AYUY2)
+    }
+
+    /// Every plane a `width * height` buffer of this format is made of, primary plane first
+    pub fn planes(&self, width: u32, height: u32) -> Vec<PlaneLayout> {
+        let plane = |plane, row_bytes, rows| PlaneLayout { plane, row_bytes, rows };
+        let (half_width, half_height) = (width.div_ceil(2), height.div_ceil(2));
+        // pixel pairs share their chroma, an odd width still takes a whole pair
+        let primary = match self {
+            Self::YUY2 | Self::AYUY2 => half_width * 4,
+            _ => (width * self.bits_per_pixel()).div_ceil(8)
+        };
+        let mut planes = vec![plane(Plane::Primary, primary, height)];
+        match self {
+            Self::NV12 | Self::ANV12 => planes.push(plane(Plane::Chroma, half_width * 2, half_height)),
+            Self::NV16 => planes.push(plane(Plane::Chroma, half_width * 2, height)),
+            Self::YV12 => {
+                planes.push(plane(Plane::Chroma, half_width, half_height));
+                planes.push(plane(Plane::V, half_width, half_height));
+            }
+            Self::YV24 => {
+                planes.push(plane(Plane::Chroma, width, height));
+                planes.push(plane(Plane::V, width, height));
+            }
+            _ => ()
+        }
+        if matches!(self, Self::ANV12 | Self::AYUY2) {
+            planes.push(plane(Plane::Alpha, width, height));
+        }
+        planes
+    }
+}